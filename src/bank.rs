@@ -0,0 +1,437 @@
+//! The native exam bank format, written as JSON or YAML.
+//!
+//! ```yaml
+//! version: 1
+//! name: Major 1
+//! questions:
+//!   - text: What is $1+1$?
+//!     group: 1
+//!     choices:
+//!       - text: "2"
+//!         correct: true
+//!       - text: "3"
+//!       - text: None of these
+//!         pinned: true
+//!     metadata:
+//!       Points: "2"
+//! ```
+//!
+//! Unknown fields are rejected so that typos do not go unnoticed; the format
+//! is described by the JSON Schema [`BANK_SCHEMA`].
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    errors::ExamReaderError,
+    shuffler::{Choice, Choices, CorrectChoice, Exam, ExamSetting, Question},
+};
+
+/// Version of the bank format written by this crate.
+pub const BANK_VERSION: u32 = 1;
+
+/// JSON Schema of the bank format, for editors validating JSON or YAML
+/// banks.
+pub const BANK_SCHEMA: &str = include_str!("../schema/exam-bank.schema.json");
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bank {
+    /// Location of the schema, for editors; ignored otherwise.
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub version: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preamble: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setting: Option<BankSetting>,
+    pub questions: Vec<BankQuestion>,
+}
+
+/// Cover page and shuffling setting of a bank; the fields of
+/// [`ExamSetting`], with the number of versions as `versions`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BankSetting {
+    pub university: String,
+    pub department: String,
+    pub term: String,
+    pub coursecode: String,
+    pub examname: String,
+    pub examdate: String,
+    pub timeallowed: String,
+    pub versions: u32,
+    pub groups: String,
+}
+
+impl Default for BankSetting {
+    fn default() -> Self {
+        ExamSetting::new().into()
+    }
+}
+
+impl From<ExamSetting> for BankSetting {
+    fn from(setting: ExamSetting) -> Self {
+        BankSetting {
+            university: setting.university,
+            department: setting.department,
+            term: setting.term,
+            coursecode: setting.coursecode,
+            examname: setting.examname,
+            examdate: setting.examdate,
+            timeallowed: setting.timeallowed,
+            versions: setting.numberofvestions,
+            groups: setting.groups,
+        }
+    }
+}
+
+impl From<BankSetting> for ExamSetting {
+    fn from(setting: BankSetting) -> Self {
+        ExamSetting {
+            university: setting.university,
+            department: setting.department,
+            term: setting.term,
+            coursecode: setting.coursecode,
+            examname: setting.examname,
+            examdate: setting.examdate,
+            timeallowed: setting.timeallowed,
+            numberofvestions: setting.versions,
+            groups: setting.groups,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BankQuestion {
+    pub text: String,
+    #[serde(default = "first_group")]
+    pub group: u32,
+    /// No choices make an open question.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<BankChoice>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BankChoice {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub correct: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pinned: bool,
+}
+
+fn first_group() -> u32 {
+    1
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Only the version, read before the rest so that banks of a newer format
+/// get a clear error instead of one about unknown fields.
+#[derive(Deserialize)]
+struct Versioned {
+    version: Option<u32>,
+}
+
+impl Bank {
+    /// The bank of `ex`, with the questions and choices in printed order.
+    pub fn from_exam(ex: &Exam, setting: Option<&ExamSetting>) -> Self {
+        let questions = ex
+            .ordered_questions()
+            .into_iter()
+            .map(|q| BankQuestion {
+                text: q.text.clone(),
+                group: q.group,
+                choices: q
+                    .choices
+                    .as_ref()
+                    .map(|cs| {
+                        let correct = cs.correct_positions();
+                        cs.ordered()
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, c)| BankChoice {
+                                text: c.text.clone(),
+                                correct: correct.contains(&(i as u32)),
+                                pinned: c.pinned,
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                metadata: q.metadata.clone(),
+            })
+            .collect();
+        Bank {
+            schema: None,
+            version: BANK_VERSION,
+            name: ex.name.clone(),
+            preamble: ex.preamble.clone(),
+            setting: setting.cloned().map(BankSetting::from),
+            questions,
+        }
+    }
+
+    /// The exam and setting of the bank. Every question with choices needs
+    /// at least one correct choice.
+    pub fn into_exam(self) -> Result<(Exam, Option<ExamSetting>), ExamReaderError> {
+        let questions = self
+            .questions
+            .into_iter()
+            .enumerate()
+            .map(|(i, q)| {
+                let order = i as u32 + 1;
+                if q.text.trim().is_empty() {
+                    return Err(ExamReaderError::TemplateError(format!(
+                        "question {} has no text",
+                        order
+                    )));
+                }
+                let choices = if q.choices.is_empty() {
+                    None
+                } else {
+                    let correct = q.choices.iter().position(|c| c.correct).ok_or_else(|| {
+                        ExamReaderError::TemplateError(format!(
+                            "question {} has choices but none is marked correct",
+                            order
+                        ))
+                    })?;
+                    let choices = q
+                        .choices
+                        .into_iter()
+                        .map(|c| Choice {
+                            text: c.text,
+                            pinned: c.pinned,
+                            correct: c.correct,
+                        })
+                        .collect();
+                    Some(Choices(choices, CorrectChoice(correct as u32), None))
+                };
+                Ok(Question {
+                    text: q.text,
+                    order,
+                    choices,
+                    group: q.group,
+                    metadata: q.metadata,
+                })
+            })
+            .collect::<Result<Vec<Question>, ExamReaderError>>()?;
+        let exam = Exam {
+            name: self.name,
+            preamble: self.preamble,
+            questions: Some(questions),
+            ordering: None,
+        };
+        Ok((exam, self.setting.map(ExamSetting::from)))
+    }
+
+    /// Reads a JSON bank; syntax errors and unknown fields are reported at
+    /// their line in `file`.
+    pub fn from_json_str(file: &str, content: &str) -> Result<Self, ExamReaderError> {
+        let error = |err: serde_json::Error| {
+            syntax_error(file, content, err.line(), err.column(), err.to_string())
+        };
+        let versioned: Versioned = serde_json::from_str(content).map_err(error)?;
+        check_version(versioned.version)?;
+        serde_json::from_str(content).map_err(error)
+    }
+
+    /// Reads a YAML bank, reporting errors like [`Bank::from_json_str`].
+    pub fn from_yaml_str(file: &str, content: &str) -> Result<Self, ExamReaderError> {
+        let error = |err: serde_yaml_ng::Error| {
+            let (line, column) = err.location().map_or((1, 1), |l| (l.line(), l.column()));
+            syntax_error(file, content, line, column, err.to_string())
+        };
+        let versioned: Versioned = serde_yaml_ng::from_str(content).map_err(error)?;
+        check_version(versioned.version)?;
+        serde_yaml_ng::from_str(content).map_err(error)
+    }
+}
+
+fn check_version(version: Option<u32>) -> Result<(), ExamReaderError> {
+    match version {
+        Some(v) if v > BANK_VERSION => Err(ExamReaderError::TemplateError(format!(
+            "the bank has format version {}, newer than the supported version {}",
+            v, BANK_VERSION
+        ))),
+        Some(0) | None => Err(ExamReaderError::TemplateError(
+            "the bank has no format `version`".to_string(),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+fn syntax_error(
+    file: &str,
+    content: &str,
+    line: usize,
+    column: usize,
+    message: String,
+) -> ExamReaderError {
+    ExamReaderError::Diagnostics(vec![Diagnostic::at_line(
+        file,
+        content,
+        line,
+        column,
+        Severity::Error,
+        message,
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::ChoiceOrdering;
+
+    fn exam() -> Exam {
+        let mut q = Question::new(
+            "Which are even?",
+            1,
+            Some(Choices(
+                vec![
+                    Choice::new("2"),
+                    Choice {
+                        correct: true,
+                        ..Choice::new("4")
+                    },
+                    Choice::pinned("None of these"),
+                ],
+                CorrectChoice(0),
+                None,
+            )),
+        );
+        q.group = 2;
+        q.metadata.insert("Points".to_string(), "2".to_string());
+        Exam {
+            questions: Some(vec![q, Question::new("Explain.", 2, None)]),
+            ..Exam::new("Major 1")
+        }
+    }
+
+    #[test]
+    fn reads_yaml_bank() {
+        let content = std::fs::read_to_string("files/testing/bank.yaml").unwrap();
+        let (ex, setting) = Bank::from_yaml_str("bank.yaml", &content)
+            .unwrap()
+            .into_exam()
+            .unwrap();
+        assert_eq!(ex.name, "Major 1");
+        assert_eq!(setting.unwrap().numberofvestions, 4);
+        let unknown = "version: 1\nname: x\nsetting:\n  numberofvestions: 4\nquestions: []\n";
+        assert!(Bank::from_yaml_str("bank.yaml", unknown).is_err());
+        let qs = ex.questions.unwrap();
+        assert_eq!(qs[0].text, "What is $1+1$?");
+        assert_eq!(qs[0].group, 1);
+        let cs = qs[0].choices.as_ref().unwrap();
+        assert_eq!(cs.correct_positions(), vec![1]);
+        assert!(cs.0[2].pinned);
+        assert_eq!(qs[0].metadata.get("Points"), Some(&"2".to_string()));
+        assert_eq!((qs[1].group, qs[1].choices.clone()), (2, None));
+    }
+
+    #[test]
+    fn round_trips_json_and_yaml() {
+        let bank = Bank::from_exam(&exam(), Some(&ExamSetting::new()));
+        let json = serde_json::to_string_pretty(&bank).unwrap();
+        assert!(json.contains("\"correct\": true"));
+        assert!(!json.contains("\"pinned\": false"));
+        assert_eq!(Bank::from_json_str("bank.json", &json).unwrap(), bank);
+        let yaml = serde_yaml_ng::to_string(&bank).unwrap();
+        assert_eq!(Bank::from_yaml_str("bank.yaml", &yaml).unwrap(), bank);
+
+        let (ex, _) = bank.into_exam().unwrap();
+        let cs = ex.questions.as_ref().unwrap()[0].choices.clone().unwrap();
+        assert_eq!(cs.correct_positions(), vec![0, 1]);
+    }
+
+    #[test]
+    fn bank_keeps_printed_order() {
+        let mut ex = exam();
+        if let Some(qs) = ex.questions.as_mut() {
+            qs[0].choices.as_mut().unwrap().2 = Some(ChoiceOrdering(vec![1, 0, 2]));
+        }
+        ex.ordering = Some(vec![1, 0]);
+        let bank = Bank::from_exam(&ex, None);
+        assert_eq!(bank.questions[0].text, "Explain.");
+        let texts: Vec<&str> = bank.questions[1]
+            .choices
+            .iter()
+            .map(|c| c.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["4", "2", "None of these"]);
+    }
+
+    #[test]
+    fn reports_bank_errors() {
+        let content =
+            "{\n  \"version\": 1,\n  \"name\": \"x\",\n  \"questions\": [{\"txt\": \"?\"}]\n}";
+        match Bank::from_json_str("bank.json", content).unwrap_err() {
+            ExamReaderError::Diagnostics(ds) => {
+                assert_eq!(ds[0].line, 4);
+                assert!(ds[0].message.starts_with("unknown field `txt`"));
+            }
+            err => panic!("unexpected error {}", err),
+        }
+        let newer = "version: 2\nname: x\nquestions: []\nsections: []\n";
+        assert_eq!(
+            Bank::from_yaml_str("bank.yaml", newer)
+                .unwrap_err()
+                .to_string(),
+            "Your input file is badly formatted: `the bank has format version 2, newer than the supported version 1`"
+        );
+        let unmarked =
+            "version: 1\nname: x\nquestions:\n  - text: Why?\n    choices:\n      - text: a\n";
+        assert!(Bank::from_yaml_str("bank.yaml", unmarked)
+            .unwrap()
+            .into_exam()
+            .is_err());
+    }
+
+    #[test]
+    fn schema_matches_the_format() {
+        let schema: serde_json::Value = serde_json::from_str(BANK_SCHEMA).unwrap();
+        assert_eq!(schema["properties"]["version"]["const"], BANK_VERSION);
+        let keys = |value: &serde_json::Value| -> Vec<String> {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let bank = serde_json::to_value(Bank {
+            schema: Some(String::new()),
+            preamble: Some(String::new()),
+            ..Bank::from_exam(&exam(), Some(&ExamSetting::new()))
+        })
+        .unwrap();
+        assert_eq!(keys(&schema["properties"]), keys(&bank));
+        assert_eq!(
+            keys(&schema["$defs"]["setting"]["properties"]),
+            keys(&bank["setting"])
+        );
+        assert_eq!(
+            keys(&schema["$defs"]["question"]["properties"]),
+            keys(&bank["questions"][0])
+        );
+        // A text of only whitespace is rejected by `into_exam` as well.
+        assert_eq!(
+            schema["$defs"]["question"]["properties"]["text"]["pattern"],
+            "\\S"
+        );
+        let mut choice_keys: Vec<String> = bank["questions"][0]["choices"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(keys)
+            .collect();
+        choice_keys.sort();
+        choice_keys.dedup();
+        assert_eq!(keys(&schema["$defs"]["choice"]["properties"]), choice_keys);
+    }
+}
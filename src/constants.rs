@@ -3,8 +3,19 @@ pub const TEX_PREAMBLE_END: &str = "%{/preamble}";
 pub const TEX_DOC_START: &str = "\\begin{document}";
 pub const TEX_DOC_END: &str = "\\end{document}";
 pub const TEX_QUESTION_START: &str = "%{#q}";
+pub const TEX_QUESTION_TAG_OPEN: &str = "%{#q";
 pub const TEX_QUESTION_END: &str = "%{/q}";
 pub const TEX_OPTION_START: &str = "%{#o}";
+pub const TEX_OPTION_TAG_OPEN: &str = "%{#o";
 pub const TEX_OPTION_END: &str = "%{/o}";
+pub const TEX_VARS_START: &str = "%{#vars}";
+pub const TEX_VARS_END: &str = "%{/vars}";
 pub const TEX_SETTING_START: &str = "%{#setting}";
 pub const TEX_SETTING_END: &str = "%{/setting}";
+pub const OPTION_PIN_MARK: &str = "!";
+pub const OPTION_PIN_ATTR: &str = "pin";
+pub const OPTION_CORRECT_MARK: &str = "*";
+pub const OPTION_CORRECT_ATTR: &str = "correct";
+pub const QUESTION_GROUP_ATTR: &str = "group";
+pub const PARAM_OPEN: &str = "@{";
+pub const PARAM_CLOSE: &str = "}";
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{constants::*, groups::question_group};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in an input file, pointing at its `line` and `column`
+/// (both 1-based). `source` is the offending line and `length` the number of
+/// characters underlined in it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub severity: Severity,
+    pub message: String,
+    pub source: String,
+}
+
+impl Diagnostic {
+    /// A diagnostic for the `length` characters at byte `offset` of `content`.
+    pub fn at(
+        file: &str,
+        content: &str,
+        offset: usize,
+        length: usize,
+        severity: Severity,
+        message: impl Into<String>,
+    ) -> Self {
+        let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = content[offset..]
+            .find('\n')
+            .map_or(content.len(), |i| offset + i);
+        Diagnostic {
+            file: file.to_string(),
+            line: content[..offset].matches('\n').count() + 1,
+            column: content[line_start..offset].chars().count() + 1,
+            length: length.max(1),
+            severity,
+            message: message.into(),
+            source: content[line_start..line_end].trim_end().to_string(),
+        }
+    }
+
+    /// A diagnostic at `line` and `column` (both 1-based) of `content`,
+    /// underlining the rest of the line.
+    pub fn at_line(
+        file: &str,
+        content: &str,
+        line: usize,
+        column: usize,
+        severity: Severity,
+        message: impl Into<String>,
+    ) -> Self {
+        let line_start: usize = content
+            .split_inclusive('\n')
+            .take(line.saturating_sub(1))
+            .map(|l| l.len())
+            .sum::<usize>()
+            .min(content.len());
+        let source = content[line_start..].lines().next().unwrap_or_default();
+        let offset = line_start
+            + source
+                .char_indices()
+                .nth(column.saturating_sub(1))
+                .map_or(source.len(), |(i, _)| i);
+        let length = source[offset - line_start..].trim_end().chars().count();
+        Diagnostic::at(file, content, offset, length, severity, message)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Renders the diagnostic the way compilers do:
+///
+/// ```text
+/// error: `%{#q}` is never closed
+///    --> exam.tex:12:1
+///    |
+/// 12 | %{#q} What is $1+1$?
+///    | ^^^^^
+/// ```
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+        writeln!(
+            f,
+            "{} --> {}:{}:{}",
+            gutter, self.file, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.source)?;
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(self.column - 1),
+            "^".repeat(self.length)
+        )
+    }
+}
+
+/// Renders `diagnostics` one after the other, separated by blank lines.
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+#[derive(Clone, Copy)]
+enum Open {
+    Question(usize, usize),
+    Option(usize, usize),
+}
+
+/// Checks the tags of a TeX template: `%{#q}` and `%{#o}` tags that are
+/// never closed, closing tags without an opening one, question tags with
+/// bad attributes, empty questions and options, and keys given twice in the
+/// `%{#setting}` block.
+///
+/// The question and option tags are only checked between `\begin{document}`
+/// and `\end{document}`; a template missing either has no diagnostics here.
+pub fn check_tex(file: &str, content: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = setting_diagnostics(file, content);
+    let (Some(start), Some(end)) = (content.find(TEX_DOC_START), content.find(TEX_DOC_END)) else {
+        return diagnostics;
+    };
+    let at = |offset: usize, length: usize, severity: Severity, message: String| {
+        Diagnostic::at(file, content, offset, length, severity, message)
+    };
+    let unclosed = |open: Open| match open {
+        Open::Question(offset, length) => at(
+            offset,
+            length,
+            Severity::Error,
+            format!(
+                "`{}` is never closed by `{}`",
+                &content[offset..offset + length],
+                TEX_QUESTION_END
+            ),
+        ),
+        Open::Option(offset, length) => at(
+            offset,
+            length,
+            Severity::Error,
+            format!(
+                "`{}` is never closed by `{}`",
+                &content[offset..offset + length],
+                TEX_OPTION_END
+            ),
+        ),
+    };
+    let stray = |offset: usize, tag: &str| {
+        at(
+            offset,
+            tag.len(),
+            Severity::Error,
+            format!("`{}` has no matching opening tag", tag),
+        )
+    };
+    let empty = |offset: usize, length: usize, what: &str| {
+        at(
+            offset,
+            length,
+            Severity::Warning,
+            format!("empty {} is ignored", what),
+        )
+    };
+
+    let mut open: Option<Open> = None;
+    let mut pos = start + TEX_DOC_START.len();
+    while let Some(i) = content[pos..end].find("%{") {
+        let offset = pos + i;
+        let rest = &content[offset..end];
+        pos = offset + 2;
+        let question_tag = rest
+            .strip_prefix(TEX_QUESTION_TAG_OPEN)
+            .is_some_and(|r| r.starts_with(|c: char| c == '}' || c.is_whitespace()));
+        if question_tag {
+            let length = rest.find('}').map_or(rest.len(), |e| e + 1);
+            if let Some(previous) = open {
+                diagnostics.push(unclosed(previous));
+            }
+            let attrs = rest[TEX_QUESTION_TAG_OPEN.len()..length].trim_end_matches('}');
+            if let Err(message) = question_group(attrs) {
+                diagnostics.push(at(offset, length, Severity::Error, message));
+            }
+            open = Some(Open::Question(offset, length));
+            pos = offset + length;
+        } else if rest.starts_with(TEX_QUESTION_END) {
+            match open {
+                Some(Open::Question(q, length)) => {
+                    let text = &content[q + length..offset];
+                    if text.trim().is_empty() {
+                        diagnostics.push(empty(q, length, "question"));
+                    }
+                }
+                _ => {
+                    if let Some(previous) = open {
+                        diagnostics.push(unclosed(previous));
+                    }
+                    diagnostics.push(stray(offset, TEX_QUESTION_END));
+                }
+            }
+            open = None;
+            pos = offset + TEX_QUESTION_END.len();
+        } else if rest.starts_with(TEX_OPTION_TAG_OPEN) {
+            let length = rest.find('}').map_or(rest.len(), |e| e + 1);
+            if let Some(previous) = open {
+                diagnostics.push(unclosed(previous));
+            }
+            open = Some(Open::Option(offset, length));
+            pos = offset + length;
+        } else if rest.starts_with(TEX_OPTION_END) {
+            match open {
+                Some(Open::Option(o, length)) => {
+                    if content[o + length..offset].trim().is_empty() {
+                        diagnostics.push(empty(o, length, "option"));
+                    }
+                }
+                _ => {
+                    if let Some(previous) = open {
+                        diagnostics.push(unclosed(previous));
+                    }
+                    diagnostics.push(stray(offset, TEX_OPTION_END));
+                }
+            }
+            open = None;
+            pos = offset + TEX_OPTION_END.len();
+        }
+    }
+    if let Some(previous) = open {
+        diagnostics.push(unclosed(previous));
+    }
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+fn setting_diagnostics(file: &str, content: &str) -> Vec<Diagnostic> {
+    let (Some(start), Some(end)) = (
+        content.find(TEX_SETTING_START),
+        content.find(TEX_SETTING_END),
+    ) else {
+        return vec![];
+    };
+    let mut diagnostics = vec![];
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut offset = start + TEX_SETTING_START.len();
+    for line in content[offset..end.max(offset)].split_inclusive('\n') {
+        let entry = line.trim().trim_start_matches('%').trim();
+        if let Some((key, _)) = entry.split_once('=') {
+            let key = key.trim();
+            let column = offset + line.find(key).unwrap_or(0);
+            let here = Diagnostic::at(file, content, column, key.len(), Severity::Warning, "");
+            match seen.get(key) {
+                Some(first) => diagnostics.push(Diagnostic {
+                    message: format!(
+                        "setting `{}` is given again; the value on line {} is overridden",
+                        key, first
+                    ),
+                    ..here
+                }),
+                None => {
+                    seen.insert(key, here.line);
+                }
+            }
+        }
+        offset += line.len();
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(content: &str) -> Vec<(usize, usize, Severity, String)> {
+        check_tex("exam.tex", content)
+            .into_iter()
+            .map(|d| (d.line, d.column, d.severity, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn well_formed_template_has_no_diagnostics() {
+        let content = std::fs::read_to_string("files/testing/exam_setting.tex").unwrap();
+        assert_eq!(check_tex("exam_setting.tex", &content), vec![]);
+    }
+
+    #[test]
+    fn reports_unclosed_and_stray_tags() {
+        let content = "\\begin{document}\n%{#q} one\n%{#o}a%{/o}\n%{#q} two %{/q}\n  %{/o}\n%{#o*}b\n\\end{document}";
+        assert_eq!(
+            messages(content),
+            vec![
+                (
+                    2,
+                    1,
+                    Severity::Error,
+                    "`%{#q}` is never closed by `%{/q}`".to_string()
+                ),
+                (
+                    5,
+                    3,
+                    Severity::Error,
+                    "`%{/o}` has no matching opening tag".to_string()
+                ),
+                (
+                    6,
+                    1,
+                    Severity::Error,
+                    "`%{#o*}` is never closed by `%{/o}`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_empty_options_and_duplicate_settings() {
+        let content = "%{#setting}\n% term = 1\n% examname = x\n% term = 2\n%{/setting}\n\\begin{document}\n%{#q} one %{/q}\n%{#o}  %{/o}\n\\end{document}";
+        assert_eq!(
+            messages(content),
+            vec![
+                (
+                    4,
+                    3,
+                    Severity::Warning,
+                    "setting `term` is given again; the value on line 2 is overridden".to_string()
+                ),
+                (
+                    8,
+                    1,
+                    Severity::Warning,
+                    "empty option is ignored".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_bad_question_attributes() {
+        let content = "\\begin{document}\n%{#q group=2} one %{/q}\n%{#q group=x} two %{/q}\n%{#q pts=1} three\n\\end{document}";
+        assert_eq!(
+            messages(content),
+            vec![
+                (
+                    3,
+                    1,
+                    Severity::Error,
+                    "the group `x` is not a number".to_string()
+                ),
+                (
+                    4,
+                    1,
+                    Severity::Error,
+                    "`pts=1` is not a question attribute, expected `group=<number>`".to_string()
+                ),
+                (
+                    4,
+                    1,
+                    Severity::Error,
+                    "`%{#q pts=1}` is never closed by `%{/q}`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_like_a_compiler() {
+        let content = "\\begin{document}\n%{#q} one\n\\end{document}";
+        assert_eq!(
+            render(&check_tex("exam.tex", content)),
+            "error: `%{#q}` is never closed by `%{/q}`\n  --> exam.tex:2:1\n  |\n2 | %{#q} one\n  | ^^^^^"
+        );
+    }
+}
@@ -1,11 +1,19 @@
 use thiserror::Error;
 
+use crate::diagnostics::{render, Diagnostic};
+
 #[derive(Error, Debug)]
 pub enum ExamReaderError {
     #[error("Reading error")]
     IOError(#[from] std::io::Error),
     #[error("Your input file is badly formatted: `{0}`")]
     TemplateError(String),
+    #[error("{}", render(.0))]
+    Diagnostics(Vec<Diagnostic>),
+    #[error("Cannot read the spreadsheet: `{0}`")]
+    SpreadsheetError(String),
+    #[error("Cannot read the package: `{0}`")]
+    PackageError(String),
     #[error("Your input file is badly `{0}` is not available")]
     Redaction(String),
     #[error("invalid header (expected {expected:?}, found {found:?})")]
@@ -13,3 +21,51 @@ pub enum ExamReaderError {
     #[error("unknown error")]
     Unknown,
 }
+
+#[derive(Error, Debug)]
+pub enum ExamWriterError {
+    #[error("Writing error")]
+    IOError(#[from] std::io::Error),
+    #[error("CSV error")]
+    CsvError(#[from] csv::Error),
+    #[error("JSON error")]
+    JsonError(#[from] serde_json::Error),
+    #[error("YAML error")]
+    YamlError(#[from] serde_yaml_ng::Error),
+    #[error("Zip error")]
+    ZipError(#[from] zip::result::ZipError),
+    #[error("The exam has no questions")]
+    NoQuestions,
+    #[error("question {question} cannot be written as {format}: {reason}")]
+    Unsupported {
+        question: u32,
+        format: String,
+        reason: String,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum ShuffleError {
+    #[error("no version `{version}` far enough from the others was found in {attempts} attempts")]
+    TooSimilar { version: String, attempts: u32 },
+    #[error("invalid group draw `{0}`, expected `group:count`")]
+    InvalidPool(String),
+    #[error("cannot draw {requested} questions from group {group}, it has {available}")]
+    PoolTooSmall {
+        group: u32,
+        requested: usize,
+        available: usize,
+    },
+    #[error("question {question}: {message}")]
+    Parameter { question: u32, message: String },
+    #[error("question {question}: no shuffle of the choices met the answer balance in {attempts} attempts")]
+    Unbalanced { question: u32, attempts: u32 },
+    #[error(
+        "question {question}: {kept} choices are correct or pinned, more than the {count} to print"
+    )]
+    TooManyKeptChoices {
+        question: u32,
+        kept: usize,
+        count: usize,
+    },
+}
@@ -1,29 +1,63 @@
+use calamine::{open_workbook, Data, Reader, Xlsx, XlsxError};
 use csv::{self};
+use std::collections::BTreeMap;
 use std::fs;
 
+pub mod aiken;
+pub mod columns;
+pub mod gift;
+pub mod moodle;
+pub mod qti;
+pub use aiken::*;
+pub use columns::*;
+pub use gift::*;
+pub use moodle::*;
+pub use qti::*;
+
 use crate::{
     constants::*,
+    diagnostics::{self, Diagnostic, Severity},
     errors::ExamReaderError,
+    groups::question_group,
     shuffler::{Choice, Choices, CorrectChoice, ExamSetting, Question},
 };
 
-pub fn from_tex(
-    filename: &str,
-) -> Result<(Option<String>, Vec<Question>, Option<ExamSetting>), ExamReaderError> {
+pub type TexExam = (Option<String>, Vec<Question>, Option<ExamSetting>);
+
+/// Reads a TeX template. Badly nested or unclosed tags make it fail with
+/// [`ExamReaderError::Diagnostics`]; use [`check_tex`] to also get the
+/// warnings.
+pub fn from_tex(filename: &str) -> Result<TexExam, ExamReaderError> {
     let filecontent = fs::read_to_string(filename);
     match filecontent {
         Ok(contnet) => match get_questions_from_tex(&contnet) {
-            Ok(cntnt) => Ok((
-                get_preamble_from_text(&contnet),
-                cntnt,
-                get_setting_from_text(&contnet),
-            )),
+            Ok(cntnt) => {
+                let errors: Vec<Diagnostic> = diagnostics::check_tex(filename, &contnet)
+                    .into_iter()
+                    .filter(|d| d.is_error())
+                    .collect();
+                if !errors.is_empty() {
+                    return Err(ExamReaderError::Diagnostics(errors));
+                }
+                Ok((
+                    get_preamble_from_text(&contnet),
+                    cntnt,
+                    get_setting_from_text(&contnet),
+                ))
+            }
             Err(err) => Err(ExamReaderError::TemplateError(err)),
         },
         Err(err) => Err(ExamReaderError::IOError(err)),
     }
 }
-fn get_setting_from_text(content: &String) -> Option<ExamSetting> {
+
+/// Errors and warnings about the tags of the TeX template `filename`.
+pub fn check_tex(filename: &str) -> Result<Vec<Diagnostic>, ExamReaderError> {
+    let content = fs::read_to_string(filename)?;
+    Ok(diagnostics::check_tex(filename, &content))
+}
+
+fn get_setting_from_text(content: &str) -> Option<ExamSetting> {
     if let Some(s) = content.find(TEX_SETTING_START) {
         if let Some(e) = content.find(TEX_SETTING_END) {
             let sttng = content[(s + 11)..e].trim().to_string();
@@ -37,18 +71,17 @@ fn get_setting_from_text(content: &String) -> Option<ExamSetting> {
                         .map(|v| v.to_string())
                         .map(|v| v.trim().to_string())
                         .collect();
-                    let key = if let Some(ks) = key_val.get(0) {
-                        let val = if let Some(vs) = key_val.get(1) {
+                    let key = if let Some(ks) = key_val.first() {
+                        if let Some(vs) = key_val.get(1) {
                             (ks.to_owned(), vs.to_owned())
                         } else {
                             (ks.to_owned(), "".to_string())
-                        };
-                        val
+                        }
                     } else {
                         ("".to_string(), "".to_string())
                     };
 
-                    return (key.0, key.1);
+                    (key.0, key.1)
                 })
                 .collect();
             let exm_setting = sertting_parts.iter().fold(ExamSetting::new(), |a, v| {
@@ -63,7 +96,7 @@ fn get_setting_from_text(content: &String) -> Option<ExamSetting> {
 
     None
 }
-fn get_preamble_from_text(content: &String) -> Option<String> {
+fn get_preamble_from_text(content: &str) -> Option<String> {
     if let Some(s) = content.find(TEX_PREAMBLE_START) {
         if let Some(e) = content.find(TEX_PREAMBLE_END) {
             let preamble = content[(s + 12)..e].trim().to_string();
@@ -75,7 +108,7 @@ fn get_preamble_from_text(content: &String) -> Option<String> {
     None
 }
 
-fn get_questions_from_tex(content: &String) -> Result<Vec<Question>, String> {
+fn get_questions_from_tex(content: &str) -> Result<Vec<Question>, String> {
     let body_start = if let Some(bdy_start) = content.find(TEX_DOC_START) {
         bdy_start + 16
     } else {
@@ -87,38 +120,43 @@ fn get_questions_from_tex(content: &String) -> Result<Vec<Question>, String> {
         return Err("The document must have \\end{document} tag".to_owned());
     };
     let body = content[body_start..body_end].to_string();
-    let parts: Vec<String> = body
-        .split(TEX_QUESTION_START)
-        .map(|p| String::from(p.trim()))
+    let parts: Vec<(&str, String)> = body
+        .split(TEX_QUESTION_TAG_OPEN)
+        .skip(1)
+        .map(|p| match p.find('}') {
+            Some(tag_end) => (&p[..tag_end], String::from(p[(tag_end + 1)..].trim())),
+            None => ("", String::new()),
+        })
         .collect();
     let mut order: u32 = 1;
     let qs: Vec<Question> = parts
         .into_iter()
-        .map(|q| {
+        .map(|(attrs, q)| {
             let body = get_question_text_from_tex(&q);
-            (body, q)
+            (attrs, body, q)
         })
-        .filter(|(b, _q)| b != "")
-        .map(|(body, q)| {
+        .filter(|(_, b, _q)| !b.is_empty())
+        .map(|(attrs, body, q)| {
             let opts = get_question_options_from_tex(&q);
             let question = Question {
                 text: body,
                 choices: opts,
                 order,
-                group: 1,
+                group: question_group(attrs).unwrap_or(1),
+                metadata: BTreeMap::new(),
             };
             order += 1;
             question
         })
         .collect();
 
-    if qs.len() == 0 {
+    if qs.is_empty() {
         return Err("No questions were found.".to_string());
     }
     Ok(qs)
 }
 
-fn get_question_text_from_tex(q: &String) -> String {
+fn get_question_text_from_tex(q: &str) -> String {
     if let Some(end_of_question_text) = q.find(TEX_QUESTION_END) {
         let text = q[..end_of_question_text].trim().to_string();
         text
@@ -127,103 +165,377 @@ fn get_question_text_from_tex(q: &String) -> String {
     }
 }
 
-fn get_question_options_from_tex(q: &String) -> Option<Choices> {
-    let parts: Vec<Choice> = q
-        .split(TEX_OPTION_START)
+fn get_question_options_from_tex(q: &str) -> Option<Choices> {
+    let parts: Vec<(OptionMarks, String)> = q
+        .split(TEX_OPTION_TAG_OPEN)
+        .skip(1)
         .map(|f| {
-            if let Some(o_end) = f.find(TEX_OPTION_END) {
-                f[..o_end].trim().to_string()
+            let (attrs, rest) = if let Some(tag_end) = f.find('}') {
+                (&f[..tag_end], &f[(tag_end + 1)..])
             } else {
-                "".to_string()
+                ("", "")
+            };
+            if let Some(o_end) = rest.find(TEX_OPTION_END) {
+                (attrs, rest[..o_end].trim().to_string())
+            } else {
+                (attrs, "".to_string())
             }
         })
-        .filter(|o| o != "")
-        .map(|o| Choice::new(&o))
+        .filter(|(_, o)| !o.is_empty())
+        .map(|(attrs, o)| (OptionMarks::from_tex_tag(attrs), o))
         .collect();
 
-    if parts.len() == 0 {
+    if parts.is_empty() {
         return None;
     }
-    Some(Choices(parts, CorrectChoice(0), None))
+    Some(choices_from_marked_options(parts))
 }
 
-pub fn from_csv(filename: &str) -> Result<Vec<Question>, ExamReaderError> {
-    let filecontent = fs::read_to_string(filename);
-    match filecontent {
-        Ok(content) => {
-            let rdr = csv::ReaderBuilder::new()
-                .has_headers(false)
-                .flexible(true)
-                .from_reader(content.as_bytes());
-
-            match get_questions_from_csv(rdr) {
-                Ok(qs) => Ok(qs),
-                Err(err) => Err(ExamReaderError::TemplateError(err)),
-            }
-        }
-        Err(err) => Err(ExamReaderError::IOError(err)),
-    }
+/// Marks put on an option by the author: pinned (`!`, `pin`) and correct
+/// (`*`, `correct`).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct OptionMarks {
+    pinned: bool,
+    correct: bool,
 }
 
-pub fn from_txt(filename: &str) -> Result<Vec<Question>, ExamReaderError> {
-    let filecontent = fs::read_to_string(filename);
-    match filecontent {
-        Ok(content) => {
-            let rdr = csv::ReaderBuilder::new()
-                .delimiter(b'\t')
-                .flexible(true)
-                .has_headers(false)
-                .from_reader(content.as_bytes());
-            match get_questions_from_csv(rdr) {
-                Ok(qs) => Ok(qs),
-                Err(err) => Err(ExamReaderError::TemplateError(err)),
+impl OptionMarks {
+    /// `attrs` is whatever sits between `%{#o` and `}`, e.g. `%{#o*}`,
+    /// `%{#o!}` or `%{#o correct pin}`.
+    fn from_tex_tag(attrs: &str) -> Self {
+        attrs
+            .split_whitespace()
+            .fold(OptionMarks::default(), |marks, attr| {
+                if attr == OPTION_PIN_ATTR {
+                    OptionMarks {
+                        pinned: true,
+                        ..marks
+                    }
+                } else if attr == OPTION_CORRECT_ATTR {
+                    OptionMarks {
+                        correct: true,
+                        ..marks
+                    }
+                } else {
+                    let (attr_marks, _) = OptionMarks::strip_from(attr);
+                    OptionMarks {
+                        pinned: marks.pinned || attr_marks.pinned,
+                        correct: marks.correct || attr_marks.correct,
+                    }
+                }
+            })
+    }
+
+    /// Splits the leading marks off a CSV/TXT cell: `*42` is correct, `!None`
+    /// is pinned and `*!None` is both.
+    fn strip_from(cell: &str) -> (Self, &str) {
+        let mut marks = OptionMarks::default();
+        let mut rest = cell.trim_start();
+        loop {
+            if let Some(r) = rest.strip_prefix(OPTION_CORRECT_MARK) {
+                marks.correct = true;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix(OPTION_PIN_MARK) {
+                marks.pinned = true;
+                rest = r;
+            } else {
+                break;
             }
         }
-        Err(err) => Err(ExamReaderError::IOError(err)),
+        if marks == OptionMarks::default() {
+            (marks, cell)
+        } else {
+            (marks, rest.trim())
+        }
     }
 }
 
-fn get_questions_from_csv(mut rdr: csv::Reader<&[u8]>) -> Result<Vec<Question>, String> {
-    let mut order = 0;
-    let qs: Vec<Question> = rdr
-        .records()
+/// The first option marked as correct is the correct choice and any further
+/// marked option is accepted too; without any mark the first option is the
+/// correct one.
+fn choices_from_marked_options(options: Vec<(OptionMarks, String)>) -> Choices {
+    let correct = options
+        .iter()
+        .position(|(marks, _)| marks.correct)
+        .unwrap_or(0);
+    let choices: Vec<Choice> = options
         .into_iter()
-        .map(|res| match res {
-            Ok(rec) => {
-                let record: Vec<String> = rec.iter().map(|f| f.to_string()).collect();
-                let choices = get_question_options_from_csv(record[2..].to_vec());
-                if let Some(text) = record.get(1) {
-                    order = order + 1;
-                    let group: u32 = if let Some(group_str) = record.get(0) {
-                        group_str.parse().unwrap_or(1)
-                    } else {
-                        1
-                    };
+        .map(|(marks, o)| Choice {
+            text: o,
+            pinned: marks.pinned,
+            correct: marks.correct,
+        })
+        .collect();
+    Choices(choices, CorrectChoice(correct as u32), None)
+}
 
-                    Question {
-                        text: text.to_owned(),
-                        order,
-                        choices: Some(choices),
-                        group,
-                    }
-                } else {
-                    Question::from("", 0)
-                }
+/// How rows that cannot be turned into a question are handled when reading
+/// CSV, TXT and XLSX files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadMode {
+    /// Any bad row fails the whole import.
+    Strict,
+    /// Bad rows, such as rows without question text or with a group that is
+    /// not a number, are skipped and reported as warnings.
+    #[default]
+    Lenient,
+}
+
+/// Questions read from a file with the warnings about the rows skipped.
+pub type ReadQuestions = (Vec<Question>, Vec<Diagnostic>);
+
+pub fn from_csv(filename: &str) -> Result<Vec<Question>, ExamReaderError> {
+    from_csv_with_options(filename, &ReadOptions::default()).map(|(qs, _)| qs)
+}
+
+pub fn from_csv_with_options(
+    filename: &str,
+    options: &ReadOptions,
+) -> Result<ReadQuestions, ExamReaderError> {
+    let content = fs::read_to_string(filename)?;
+    read_delimited(filename, &content, options.csv_delimiter, options)
+}
+
+pub fn from_txt(filename: &str) -> Result<Vec<Question>, ExamReaderError> {
+    from_txt_with_options(filename, &ReadOptions::default()).map(|(qs, _)| qs)
+}
+
+pub fn from_txt_with_options(
+    filename: &str,
+    options: &ReadOptions,
+) -> Result<ReadQuestions, ExamReaderError> {
+    let content = fs::read_to_string(filename)?;
+    read_delimited(filename, &content, b'\t', options)
+}
+
+fn read_delimited(
+    filename: &str,
+    content: &str,
+    delimiter: u8,
+    options: &ReadOptions,
+) -> Result<ReadQuestions, ExamReaderError> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let records = rdr.records().map(|res| match res {
+        Ok(rec) => (
+            rec.position().map_or(0, |p| p.line() as usize),
+            Ok(rec.iter().map(|f| f.to_string()).collect()),
+        ),
+        Err(err) => (
+            err.position().map_or(0, |p| p.line() as usize),
+            Err(err.to_string()),
+        ),
+    });
+    let (qs, problems) = get_questions_from_records(records, options);
+    finish_rows(filename, content, qs, problems, options.mode)
+}
+
+/// Reads questions from the worksheet `sheet` (the first one when `None`)
+/// using the CSV layout: group, question text and then the options.
+///
+/// Formula cells contribute their cached result, whole numbers are read
+/// without a decimal part and empty trailing cells are dropped. A first row
+/// naming the columns is a header as in CSV files; a blank group is group 1.
+pub fn from_xlsx(filename: &str, sheet: Option<&str>) -> Result<Vec<Question>, ExamReaderError> {
+    from_xlsx_with_options(filename, sheet, &ReadOptions::default()).map(|(qs, _)| qs)
+}
+
+/// Like [`from_xlsx`]; the diagnostics point at the worksheet rows, with the
+/// cells separated by tabs.
+pub fn from_xlsx_with_options(
+    filename: &str,
+    sheet: Option<&str>,
+    options: &ReadOptions,
+) -> Result<ReadQuestions, ExamReaderError> {
+    let mut workbook: Xlsx<_> = open_workbook(filename).map_err(xlsx_error)?;
+    let sheet_name = match sheet {
+        Some(name) => name.to_string(),
+        None => match workbook.sheet_names().first() {
+            Some(name) => name.to_owned(),
+            None => {
+                return Err(ExamReaderError::TemplateError(
+                    "the workbook has no worksheets".to_string(),
+                ))
+            }
+        },
+    };
+    let range = workbook.worksheet_range(&sheet_name).map_err(xlsx_error)?;
+    let rows: Vec<Vec<String>> = range
+        .rows()
+        .map(|row| {
+            let mut record: Vec<String> = row.iter().map(xlsx_cell_to_string).collect();
+            while record.last().is_some_and(|c| c.is_empty()) {
+                record.pop();
             }
-            Err(_err) => Question::from("", 0),
+            record
         })
-        .filter(|q| q.text != "")
         .collect();
+    let content: String = rows
+        .iter()
+        .map(|r| r.join("\t"))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let records = rows.into_iter().enumerate().map(|(i, r)| (i + 1, Ok(r)));
+    let (qs, problems) = get_questions_from_records(records, options);
+    let file = format!("{}[{}]", filename, sheet_name);
+    finish_rows(&file, &content, qs, problems, options.mode)
+}
 
-    if qs.len() == 0 {
-        return Err("no questions were found".to_string());
+fn xlsx_cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::Error(_) => String::new(),
+        _ => cell.to_string().trim().to_string(),
     }
-    Ok(qs)
 }
 
-fn get_question_options_from_csv(options: Vec<String>) -> Choices {
-    let choices: Vec<Choice> = options.into_iter().map(|o| Choice { text: o }).collect();
-    Choices(choices, CorrectChoice(0), None)
+fn xlsx_error(err: XlsxError) -> ExamReaderError {
+    match err {
+        XlsxError::Io(err) => ExamReaderError::IOError(err),
+        err => ExamReaderError::SpreadsheetError(err.to_string()),
+    }
+}
+
+/// Turns the `(line, message)` problems of the rows into diagnostics: errors
+/// failing the import in strict mode, warnings otherwise. When every row is
+/// skipped, the problems are errors in either mode.
+fn finish_rows(
+    file: &str,
+    content: &str,
+    qs: Vec<Question>,
+    problems: Vec<(usize, String)>,
+    mode: ReadMode,
+) -> Result<ReadQuestions, ExamReaderError> {
+    let severity = match mode {
+        ReadMode::Strict => Severity::Error,
+        ReadMode::Lenient => Severity::Warning,
+    };
+    let found: Vec<Diagnostic> = problems
+        .into_iter()
+        .map(|(line, message)| Diagnostic::at_line(file, content, line, 1, severity, message))
+        .collect();
+    if (mode == ReadMode::Strict || qs.is_empty()) && !found.is_empty() {
+        let errors = found
+            .into_iter()
+            .map(|d| Diagnostic {
+                severity: Severity::Error,
+                ..d
+            })
+            .collect();
+        return Err(ExamReaderError::Diagnostics(errors));
+    }
+    if qs.is_empty() {
+        return Err(ExamReaderError::TemplateError(
+            "no questions were found".to_string(),
+        ));
+    }
+    Ok((qs, found))
+}
+
+/// Builds questions from rows, each given with the line it starts on. A
+/// first row naming the columns as described by `read_options.columns` is a
+/// header; otherwise the rows hold group, text and options. Rows that cannot
+/// be read, have no question text, a group that is not a number or an answer
+/// that cannot be read are reported as `(line, message)`; rows of empty cells
+/// are skipped. In lenient mode, a group that is not a number is read as
+/// group 1 instead of skipping the row.
+fn get_questions_from_records(
+    records: impl Iterator<Item = (usize, Result<Vec<String>, String>)>,
+    read_options: &ReadOptions,
+) -> (Vec<Question>, Vec<(usize, String)>) {
+    let mut records = records.enumerate().peekable();
+    let first = match records.peek() {
+        Some((_, (_, Ok(first)))) => first.clone(),
+        _ => vec![],
+    };
+    let header = read_options.columns.columns(&first);
+    if header.is_some() {
+        records.next();
+    }
+    let columns = header.unwrap_or_else(|| Columns::positional(&first));
+    let mut qs = vec![];
+    let mut problems = vec![];
+    for (row, (line, record)) in records {
+        let row = row + 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                problems.push((line, format!("row {} cannot be read: {}", row, err)));
+                continue;
+            }
+        };
+        if record.iter().all(|c| c.trim().is_empty()) {
+            continue;
+        }
+        let text = match record.get(columns.text).filter(|t| !t.trim().is_empty()) {
+            Some(text) => text,
+            None => {
+                problems.push((line, format!("row {} has no question text", row)));
+                continue;
+            }
+        };
+        let group = match columns.group.and_then(|g| record.get(g)).map(|g| g.trim()) {
+            Some("") | None => 1,
+            Some(group) => match group.parse() {
+                Ok(group) => group,
+                Err(_) => {
+                    problems.push((
+                        line,
+                        format!(
+                            "row {} has the group `{}`, which is not a number",
+                            row, group
+                        ),
+                    ));
+                    continue;
+                }
+            },
+        };
+        let mut options: Vec<(OptionMarks, String)> = columns
+            .options(&record)
+            .iter()
+            .map(|o| {
+                let (marks, text) = OptionMarks::strip_from(o);
+                (marks, text.to_string())
+            })
+            .collect();
+        let answer = columns
+            .answer
+            .and_then(|a| record.get(a))
+            .filter(|a| !a.trim().is_empty());
+        if let Some(answer) = answer {
+            let texts: Vec<String> = options.iter().map(|(_, o)| o.to_owned()).collect();
+            match answer_positions(answer, &texts, read_options.columns.answer_format) {
+                Ok(positions) => positions
+                    .into_iter()
+                    .for_each(|p| options[p].0.correct = true),
+                Err(reason) => {
+                    problems.push((
+                        line,
+                        format!("row {} has the answer `{}`, {}", row, answer, reason),
+                    ));
+                    continue;
+                }
+            }
+        }
+        let metadata = columns
+            .metadata
+            .iter()
+            .filter_map(|(i, name)| {
+                let value = record.get(*i).filter(|v| !v.trim().is_empty())?;
+                Some((name.to_owned(), value.trim().to_string()))
+            })
+            .collect();
+        qs.push(Question {
+            text: text.to_owned(),
+            order: qs.len() as u32 + 1,
+            choices: Some(choices_from_marked_options(options)),
+            group,
+            metadata,
+        });
+    }
+    (qs, problems)
 }
 
 #[cfg(test)]
@@ -272,7 +584,7 @@ mod tests {
             20,
             "testing first question with different options"
         );
-        let qs1 = match tex.get(0) {
+        let qs1 = match tex.first() {
             Some(q) => match &q.choices {
                 Some(op) => op.0.len(),
                 None => 0,
@@ -342,7 +654,7 @@ mod tests {
             6,
             "testing first question with different options"
         );
-        let qs1 = match tex.get(0) {
+        let qs1 = match tex.first() {
             Some(q) => match &q.choices {
                 Some(op) => op.0.len(),
                 None => 0,
@@ -370,6 +682,305 @@ mod tests {
         assert_eq!(qs3, 0, "testing first question with different options")
     }
 
+    #[test]
+    fn read_from_csv_malformed_rows_strict() {
+        let err = from_csv_with_options(
+            "files/testing/sample-malformed.csv",
+            &ReadOptions {
+                mode: ReadMode::Strict,
+                ..ReadOptions::default()
+            },
+        )
+        .unwrap_err();
+        let found: Vec<(usize, String)> = match err {
+            ExamReaderError::Diagnostics(ds) => {
+                ds.into_iter().map(|d| (d.line, d.message)).collect()
+            }
+            err => panic!("unexpected error {}", err),
+        };
+        assert_eq!(
+            found,
+            vec![
+                (2, "row 2 has no question text".to_string()),
+                (3, "row 3 has no question text".to_string()),
+                (
+                    4,
+                    "row 4 has the group `x`, which is not a number".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_from_csv_malformed_rows_lenient() {
+        let (qs, warnings) = from_csv_with_options(
+            "files/testing/sample-malformed.csv",
+            &ReadOptions::default(),
+        )
+        .unwrap();
+        let texts: Vec<(u32, u32, &str)> = qs
+            .iter()
+            .map(|q| (q.order, q.group, q.text.as_str()))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![(1, 1, "Which one is prime?"), (2, 2, "Which one is zero?")]
+        );
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings.iter().all(|w| w.severity == Severity::Warning));
+        assert_eq!(warnings[2].source, "x,\"Which one is even?\",\"3\",\"8\"");
+        assert_eq!(
+            warnings[2].message,
+            "row 4 has the group `x`, which is not a number"
+        );
+    }
+
+    #[test]
+    fn read_from_csv_with_header() {
+        let qs = from_csv("files/testing/sample-header.csv").unwrap();
+        let read: Vec<(u32, usize, Vec<u32>, Option<&String>)> = qs
+            .iter()
+            .map(|q| {
+                let cs = q.choices.as_ref().unwrap();
+                (
+                    q.group,
+                    cs.0.len(),
+                    cs.correct_positions(),
+                    q.metadata.get("Points"),
+                )
+            })
+            .collect();
+        assert_eq!(
+            read,
+            vec![
+                (1, 4, vec![2], Some(&"2".to_string())),
+                (2, 4, vec![1, 3], None),
+                (1, 3, vec![0], Some(&"1".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_rows_without_group_column() {
+        let content = "What is 1+1?,2,3\nWhat is 2+2?,3,*4\n";
+        let (qs, warnings) =
+            read_delimited("nogroup.csv", content, b',', &ReadOptions::default()).unwrap();
+        assert!(warnings.is_empty());
+        let read: Vec<(u32, &str, Vec<u32>)> = qs
+            .iter()
+            .map(|q| {
+                let cs = q.choices.as_ref().unwrap();
+                (q.group, q.text.as_str(), cs.correct_positions())
+            })
+            .collect();
+        assert_eq!(
+            read,
+            vec![(1, "What is 1+1?", vec![0]), (1, "What is 2+2?", vec![1])]
+        );
+        let qs = from_csv("files/new_ones/sample.csv").unwrap();
+        assert!(qs[0].text.starts_with("Question 1"));
+        assert_eq!(qs[0].choices.as_ref().unwrap().0[0].text, "Answer 1");
+        let qs = from_txt("files/new_ones/sample.txt").unwrap();
+        assert_eq!(qs[1].text, "Question 2 text goes here");
+    }
+
+    #[test]
+    fn read_from_csv_with_delimiter() {
+        let tabs = ReadOptions {
+            csv_delimiter: b'\t',
+            ..ReadOptions::default()
+        };
+        let (qs, _) = from_csv_with_options("files/exam1.csv", &tabs).unwrap();
+        assert_eq!(qs[0].text, "Question 1 text goes here");
+        assert_eq!(qs[0].choices.as_ref().unwrap().0.len(), 5);
+        let (qs, _) = from_csv_with_options("files/exam1.csv", &ReadOptions::default()).unwrap();
+        assert!(qs[0].choices.as_ref().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn read_from_csv_with_unknown_answer() {
+        let content = "Question,Option A,Option B,Answer\nWhich?,a,b,C\n";
+        let options = ReadOptions {
+            mode: ReadMode::Strict,
+            ..ReadOptions::default()
+        };
+        let err = read_delimited("answer.csv", content, b',', &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("row 2 has the answer `C`, which names no option"));
+        let err = read_delimited("answer.csv", content, b',', &ReadOptions::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("error: row 2 has the answer `C`, which names no option"));
+    }
+
+    #[test]
+    fn read_from_csv_pinned_options() {
+        let filename = "files/testing/sample-pinned.csv";
+        let qs = from_csv(filename).unwrap_or_default();
+        let opts: Vec<(String, bool)> = match qs.get(1).and_then(|q| q.choices.as_ref()) {
+            Some(cs) => cs.0.iter().map(|c| (c.text.to_owned(), c.pinned)).collect(),
+            None => vec![],
+        };
+        assert_eq!(
+            opts,
+            vec![
+                ("3".to_string(), false),
+                ("5".to_string(), false),
+                ("8".to_string(), false),
+                ("All of the above".to_string(), true),
+            ],
+            "testing pinned options in csv"
+        );
+    }
+
+    #[test]
+    fn read_tex_pinned_options() {
+        let q = "Which one?%{/q} %{#o}a%{/o} %{#o!}b%{/o} %{#o pin}None of the above%{/o}";
+        let pinned: Vec<bool> = match get_question_options_from_tex(q) {
+            Some(cs) => cs.0.iter().map(|c| c.pinned).collect(),
+            None => vec![],
+        };
+        assert_eq!(
+            pinned,
+            vec![false, true, true],
+            "testing pinned options in tex"
+        );
+    }
+
+    #[test]
+    fn read_from_xlsx_bad_file() {
+        let filename = "files/testing/samples.xlsx";
+        let tex = match from_xlsx(filename, None) {
+            Ok(_) => "nothing".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        assert_eq!(
+            tex,
+            "Reading error".to_string(),
+            "testing the file does not exist"
+        )
+    }
+
+    #[test]
+    fn read_from_xlsx() {
+        let filename = "files/new_ones/sample.xlsx";
+        let qs = from_xlsx(filename, Some("Sheet1")).unwrap_or_default();
+        assert_eq!(qs.len(), 22, "testing number of questions in xlsx");
+        let first = qs.first().map(|q| (q.group, q.text.to_owned()));
+        assert_eq!(
+            first,
+            Some((1, "Question 1 $\\int\\,_0^1$ text goes here".to_string())),
+            "testing header is skipped and numeric group is read"
+        );
+        let opts = qs
+            .first()
+            .and_then(|q| q.choices.as_ref())
+            .map(|cs| cs.0.len());
+        assert_eq!(opts, Some(5), "testing options in xlsx");
+    }
+
+    #[test]
+    fn read_from_xlsx_without_header() {
+        let filename = "files/testing/sample-no-header.xlsx";
+        let qs = from_xlsx(filename, None).unwrap_or_default();
+        let read: Vec<(u32, &str)> = qs.iter().map(|q| (q.group, q.text.as_str())).collect();
+        assert_eq!(
+            read,
+            vec![(1, "What is 1+1?"), (2, "Which one is prime?")],
+            "testing a first row with a blank group is a question"
+        );
+    }
+
+    #[test]
+    fn read_from_xlsx_missing_sheet() {
+        let filename = "files/new_ones/sample.xlsx";
+        assert!(from_xlsx(filename, Some("NoSuchSheet")).is_err());
+    }
+
+    #[test]
+    fn read_from_csv_correct_options() {
+        let filename = "files/testing/sample-correct.csv";
+        let qs = from_csv(filename).unwrap_or_default();
+        let correct: Vec<Option<CorrectChoice>> = qs
+            .iter()
+            .map(|q| q.choices.as_ref().map(|cs| cs.1.to_owned()))
+            .collect();
+        assert_eq!(
+            correct,
+            vec![
+                Some(CorrectChoice(2)),
+                Some(CorrectChoice(0)),
+                Some(CorrectChoice(3))
+            ],
+            "testing correct options in csv"
+        );
+        let last = qs.get(2).and_then(|q| q.choices.as_ref()).map(|cs| {
+            cs.0.iter()
+                .map(|c| (c.text.to_owned(), c.pinned))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(
+            last,
+            Some(vec![
+                ("1".to_string(), false),
+                ("2".to_string(), false),
+                ("3".to_string(), false),
+                ("None of the above".to_string(), true),
+            ]),
+            "testing marks are removed from the option text"
+        );
+    }
+
+    #[test]
+    fn read_tex_correct_options() {
+        let correct = |q: &str| get_question_options_from_tex(q).map(|cs| cs.1);
+        assert_eq!(
+            correct("Which?%{/q} %{#o}a%{/o} %{#o*}b%{/o} %{#o}c%{/o}"),
+            Some(CorrectChoice(1))
+        );
+        assert_eq!(
+            correct("Which?%{/q} %{#o}a%{/o} %{#o}b%{/o} %{#o correct pin}c%{/o}"),
+            Some(CorrectChoice(2))
+        );
+        assert_eq!(
+            correct("Which?%{/q} %{#o}a%{/o} %{#o}b%{/o}"),
+            Some(CorrectChoice(0))
+        );
+    }
+
+    #[test]
+    fn read_tex_multiple_correct_options() {
+        let cs = get_question_options_from_tex(
+            "Which?%{/q} %{#o}a%{/o} %{#o*}b%{/o} %{#o}c%{/o} %{#o correct}d%{/o}",
+        );
+        assert_eq!(cs.map(|cs| cs.correct_positions()), Some(vec![1, 3]));
+    }
+
+    #[test]
+    fn read_tex_parametric_question() {
+        let q = "%{#vars}\n% a = 1..9\n%{/vars}\nWhat is $@{a}+1$?%{/q} %{#o*}$@{a+1}$%{/o} %{#o}$@{a}$%{/o}";
+        let text = get_question_text_from_tex(q);
+        assert!(text.starts_with("%{#vars}") && text.ends_with("What is $@{a}+1$?"));
+        let options: Vec<String> = get_question_options_from_tex(q)
+            .map(|cs| cs.0.into_iter().map(|c| c.text).collect())
+            .unwrap_or_default();
+        assert_eq!(options, vec!["$@{a+1}$", "$@{a}$"]);
+    }
+
+    #[test]
+    fn read_tex_unclosed_question() {
+        let err = from_tex("files/testing/template-unclosed-question.tex").unwrap_err();
+        match err {
+            ExamReaderError::Diagnostics(ds) => {
+                let found: Vec<(usize, usize)> = ds.iter().map(|d| (d.line, d.column)).collect();
+                assert_eq!(found, vec![(7, 1)]);
+                assert!(diagnostics::render(&ds).contains("7 | %{#q}"));
+            }
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
     #[test]
     fn read_from_tex_bad_file() {
         //bad file
@@ -466,7 +1077,7 @@ mod tests {
     fn read_from_tex_number_of_options_is_zero() {
         match read_from_tex() {
             Ok(tex) => {
-                let no_options_1: i32 = match tex.2.get(0) {
+                let no_options_1: i32 = match tex.2.first() {
                     Some(op) => match &op.choices {
                         Some(opts) => opts.0.len() as i32,
                         None => 0,
@@ -500,10 +1111,7 @@ mod tests {
     fn read_from_tex_setting_full() {
         let filename = "files/testing/exam_setting.tex";
         let exammatch = match from_tex(filename) {
-            Ok((_, _, es)) => match es {
-                Some(exam_setting) => exam_setting,
-                None => ExamSetting::new(),
-            },
+            Ok((_, _, es)) => es.unwrap_or_default(),
             Err(_err) => ExamSetting::new(),
         };
         assert_eq!(
@@ -527,10 +1135,7 @@ mod tests {
     fn read_from_tex_setting_partial() {
         let filename = "files/testing/exam_setting_withmissing_ones.tex";
         let exammatch = match from_tex(filename) {
-            Ok((_, _, es)) => match es {
-                Some(exam_setting) => exam_setting,
-                None => ExamSetting::new(),
-            },
+            Ok((_, _, es)) => es.unwrap_or_default(),
             Err(_err) => ExamSetting::new(),
         };
         assert_eq!(
@@ -554,10 +1159,7 @@ mod tests {
     fn read_from_tex_setting_empty() {
         let filename = "files/testing/template.tex";
         let exammatch = match from_tex(filename) {
-            Ok((_, _, es)) => match es {
-                Some(exam_setting) => exam_setting,
-                None => ExamSetting::new(),
-            },
+            Ok((_, _, es)) => es.unwrap_or_default(),
             Err(_err) => ExamSetting::new(),
         };
         assert_eq!(
@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use super::{choices_from_marked_options, OptionMarks};
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    errors::ExamReaderError,
+    shuffler::Question,
+};
+
+/// Reads an Aiken file: the question text, one `A. ...` or `A) ...` line per
+/// option and an `ANSWER: B` line naming the correct option.
+///
+/// Options must be labelled `A`, `B`, ... in order. Out of order labels,
+/// answers naming no option and questions without an `ANSWER:` line fail the
+/// import with [`ExamReaderError::Diagnostics`] pointing at the line.
+pub fn from_aiken(filename: &str) -> Result<Vec<Question>, ExamReaderError> {
+    let content = fs::read_to_string(filename)?;
+    read_aiken(filename, &content)
+}
+
+#[derive(Default)]
+struct AikenQuestion {
+    line: usize,
+    text: Vec<String>,
+    options: Vec<String>,
+}
+
+fn read_aiken(file: &str, content: &str) -> Result<Vec<Question>, ExamReaderError> {
+    let mut qs: Vec<Question> = vec![];
+    let mut errors = vec![];
+    let error = |line: usize, message: String| {
+        Diagnostic::at_line(file, content, line, 1, Severity::Error, message)
+    };
+    let unanswered =
+        |q: &AikenQuestion| error(q.line, "question has no `ANSWER:` line".to_string());
+    let mut current: Option<AikenQuestion> = None;
+    for (i, line) in content.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(answer) = answer_line(line) {
+            let Some(q) = current.take().filter(|q| !q.options.is_empty()) else {
+                errors.push(error(
+                    line_number,
+                    "`ANSWER:` comes before any option".to_string(),
+                ));
+                continue;
+            };
+            let position = match answer.chars().collect::<Vec<char>>()[..] {
+                [letter] if letter.is_ascii_alphabetic() => {
+                    Some((letter.to_ascii_uppercase() as u8 - b'A') as usize)
+                }
+                _ => None,
+            }
+            .filter(|p| *p < q.options.len());
+            let Some(position) = position else {
+                errors.push(error(
+                    line_number,
+                    format!("the answer `{}` names no option", answer),
+                ));
+                continue;
+            };
+            let options = q
+                .options
+                .into_iter()
+                .enumerate()
+                .map(|(i, o)| {
+                    let marks = OptionMarks {
+                        correct: i == position,
+                        ..OptionMarks::default()
+                    };
+                    (marks, o)
+                })
+                .collect();
+            qs.push(Question {
+                text: q.text.join("\n"),
+                order: qs.len() as u32 + 1,
+                choices: Some(choices_from_marked_options(options)),
+                group: 1,
+                metadata: BTreeMap::new(),
+            });
+            continue;
+        }
+        match (option_line(line), current.as_mut()) {
+            (Some((label, option)), Some(q)) => {
+                let expected = (b'A' + q.options.len() as u8) as char;
+                if label != expected {
+                    let message = match q.options.len() {
+                        0 => format!("the first option is labelled `{}`; expected `A`", label),
+                        n => format!(
+                            "option `{}` comes after `{}`; expected `{}`",
+                            label,
+                            (b'A' + n as u8 - 1) as char,
+                            expected
+                        ),
+                    };
+                    errors.push(error(line_number, message));
+                }
+                q.options.push(option.to_string());
+            }
+            (_, Some(q)) if q.options.is_empty() => q.text.push(line.to_string()),
+            (_, q) => {
+                if let Some(q) = q {
+                    errors.push(unanswered(q));
+                }
+                current = Some(AikenQuestion {
+                    line: line_number,
+                    text: vec![line.to_string()],
+                    ..AikenQuestion::default()
+                });
+            }
+        }
+    }
+    if let Some(q) = &current {
+        errors.push(unanswered(q));
+    }
+    if !errors.is_empty() {
+        return Err(ExamReaderError::Diagnostics(errors));
+    }
+    if qs.is_empty() {
+        return Err(ExamReaderError::TemplateError(
+            "no questions were found".to_string(),
+        ));
+    }
+    Ok(qs)
+}
+
+/// The letter after `ANSWER:`.
+fn answer_line(line: &str) -> Option<&str> {
+    let (key, answer) = line.split_once(':')?;
+    key.trim()
+        .eq_ignore_ascii_case("answer")
+        .then_some(answer.trim())
+}
+
+/// The label and text of an `A. text` or `A) text` line.
+fn option_line(line: &str) -> Option<(char, &str)> {
+    let mut chars = line.chars();
+    let label = chars.next().filter(|c| c.is_ascii_uppercase())?;
+    let rest = chars.as_str();
+    let text = rest.strip_prefix('.').or_else(|| rest.strip_prefix(')'))?;
+    text.starts_with(char::is_whitespace)
+        .then_some((label, text.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_aiken_file() {
+        let qs = from_aiken("files/testing/sample.aiken").unwrap();
+        let read: Vec<(&str, Vec<u32>, Vec<&str>)> = qs
+            .iter()
+            .map(|q| {
+                let cs = q.choices.as_ref().unwrap();
+                (
+                    q.text.as_str(),
+                    cs.correct_positions(),
+                    cs.0.iter().map(|c| c.text.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            read,
+            vec![
+                ("What is $1+1$?", vec![1], vec!["1", "2", "3"]),
+                (
+                    "Which city is the capital of France?\nPick one.",
+                    vec![2],
+                    vec!["Lyon", "Nice", "Paris", "Lille"]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_inconsistent_labels() {
+        let content =
+            "First?\nA. 1\nC. 2\nANSWER: A\n\nSecond?\nA) x\nB) y\nANSWER: D\n\nThird?\nA. z";
+        match read_aiken("bad.aiken", content).unwrap_err() {
+            ExamReaderError::Diagnostics(ds) => {
+                let found: Vec<(usize, &str)> =
+                    ds.iter().map(|d| (d.line, d.message.as_str())).collect();
+                assert_eq!(
+                    found,
+                    vec![
+                        (3, "option `C` comes after `A`; expected `B`"),
+                        (9, "the answer `D` names no option"),
+                        (11, "question has no `ANSWER:` line"),
+                    ]
+                );
+            }
+            err => panic!("unexpected error {}", err),
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use super::ReadMode;
+use crate::examwriter::choice_letter;
+
+/// Options of the CSV, TXT and XLSX readers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadOptions {
+    pub mode: ReadMode,
+    pub columns: ColumnMapping,
+    /// Field separator of CSV files, a comma by default; TXT files are
+    /// always tab separated.
+    pub csv_delimiter: u8,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            mode: ReadMode::default(),
+            columns: ColumnMapping::default(),
+            csv_delimiter: b',',
+        }
+    }
+}
+
+/// Header names, compared case-insensitively, by which the columns of a file
+/// with a header row are found. The other named columns are kept as question
+/// metadata.
+///
+/// A first row is a header when one of its cells is a `text` name; files
+/// without a header keep the group, text, options layout, or the text,
+/// options layout when their first cell is not a number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMapping {
+    pub group: Vec<String>,
+    pub text: Vec<String>,
+    /// Prefixes of the option columns, e.g. `option` for `Option A`.
+    pub options: Vec<String>,
+    /// The correct option(s), given by letter (`B`, `AC`), 1-based number
+    /// (`2`, `1,3`) or text, as set by `answer_format`.
+    pub answer: Vec<String>,
+    pub answer_format: AnswerFormat,
+}
+
+/// How the cells of the answer column name the correct options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnswerFormat {
+    /// By position or by text; an answer naming different options either
+    /// way, such as `4` among the options `4, 6, 7, 8`, is reported.
+    #[default]
+    Auto,
+    /// By letter or 1-based number.
+    Position,
+    /// By the text of the option.
+    Text,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        ColumnMapping {
+            group: names(&["group"]),
+            text: names(&["question", "text", "question text", "stem"]),
+            options: names(&["option", "choice"]),
+            answer: names(&["answer", "correct", "correct answer", "key"]),
+            answer_format: AnswerFormat::default(),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// Replaces the names of one field from `field=Name`, where the field is
+    /// `group`, `text`, `options` or `answer`; several names are separated
+    /// by `|`.
+    pub fn set(&mut self, assignment: &str) -> Result<(), String> {
+        let (field, names) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("`{}` is not a `field=Name` column", assignment))?;
+        let names: Vec<String> = names.split('|').map(|n| n.trim().to_string()).collect();
+        match field.trim() {
+            "group" => self.group = names,
+            "text" => self.text = names,
+            "options" => self.options = names,
+            "answer" => self.answer = names,
+            field => return Err(format!("unknown column field `{}`", field)),
+        }
+        Ok(())
+    }
+
+    /// Where the fields are in rows below `header`, or `None` when `header`
+    /// has no question text column.
+    pub(crate) fn columns(&self, header: &[String]) -> Option<Columns> {
+        let is = |names: &[String], cell: &str| {
+            names
+                .iter()
+                .any(|n| n.trim().eq_ignore_ascii_case(cell.trim()))
+        };
+        let text = header.iter().position(|c| is(&self.text, c))?;
+        let group = header.iter().position(|c| is(&self.group, c));
+        let answer = header.iter().position(|c| is(&self.answer, c));
+        let options: Vec<usize> = header
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| {
+                let c = c.trim().to_lowercase();
+                Some(*i) != answer
+                    && *i != text
+                    && self
+                        .options
+                        .iter()
+                        .any(|p| c.starts_with(&p.trim().to_lowercase()))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let metadata = header
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| {
+                !c.trim().is_empty()
+                    && *i != text
+                    && Some(*i) != group
+                    && Some(*i) != answer
+                    && !options.contains(i)
+            })
+            .map(|(i, c)| (i, c.trim().to_string()))
+            .collect();
+        Some(Columns {
+            group,
+            text,
+            options: Some(options),
+            answer,
+            metadata,
+        })
+    }
+}
+
+/// Where the fields of a question are in a row.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Columns {
+    pub group: Option<usize>,
+    pub text: usize,
+    /// `None` for every column after the text, as in files without header.
+    pub options: Option<Vec<usize>>,
+    pub answer: Option<usize>,
+    pub metadata: Vec<(usize, String)>,
+}
+
+impl Columns {
+    /// Group, question text and then the options of a file without header;
+    /// when the cell of `first`, the first row, is not a number, the file
+    /// has no group column and starts with the question text.
+    pub fn positional(first: &[String]) -> Self {
+        let cell = first.first().map_or("", |c| c.trim());
+        let grouped = cell.is_empty() || cell.parse::<u32>().is_ok();
+        Columns {
+            group: grouped.then_some(0),
+            text: usize::from(grouped),
+            options: None,
+            answer: None,
+            metadata: vec![],
+        }
+    }
+
+    /// The option cells of `record`. Named option columns left empty are
+    /// dropped when they come after the last filled one.
+    pub fn options(&self, record: &[String]) -> Vec<String> {
+        match &self.options {
+            None => record.get(self.text + 1..).unwrap_or_default().to_vec(),
+            Some(columns) => {
+                let mut cells: Vec<String> = columns
+                    .iter()
+                    .map(|i| record.get(*i).cloned().unwrap_or_default())
+                    .collect();
+                while cells.last().is_some_and(|c| c.trim().is_empty()) {
+                    cells.pop();
+                }
+                cells
+            }
+        }
+    }
+}
+
+/// Positions of the options named by an answer cell read as `format`: the
+/// text of an option, letters (`B`, `AC`, `A, C`) or 1-based numbers (`2`,
+/// `1;3`). The error completes "the answer `...`" with why it cannot be read.
+pub(crate) fn answer_positions(
+    answer: &str,
+    options: &[String],
+    format: AnswerFormat,
+) -> Result<Vec<usize>, String> {
+    let answer = answer.trim();
+    let by_text = options
+        .iter()
+        .position(|o| o.trim() == answer)
+        .map(|i| vec![i]);
+    let by_position = || positions_of(answer, options.len());
+    let positions = match format {
+        AnswerFormat::Text => by_text,
+        AnswerFormat::Position => by_position(),
+        AnswerFormat::Auto => match (by_text, by_position()) {
+            (Some(text), Some(position)) if text != position => {
+                return Err(format!(
+                    "which is option {} by position but option {} by text; set the answer format",
+                    letters(&position),
+                    letters(&text)
+                ))
+            }
+            (text, position) => text.or(position),
+        },
+    };
+    positions.ok_or_else(|| "which names no option".to_string())
+}
+
+fn letters(positions: &[usize]) -> String {
+    positions
+        .iter()
+        .map(|p| choice_letter(*p as u32).to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Positions named by letters or 1-based numbers among `nooptions` options.
+fn positions_of(answer: &str, nooptions: usize) -> Option<Vec<usize>> {
+    let parts: Vec<&str> = answer
+        .split([',', ';', ' '])
+        .filter(|p| !p.is_empty())
+        .collect();
+    let positions: Option<Vec<usize>> = if parts.iter().all(|p| p.parse::<usize>().is_ok()) {
+        parts
+            .iter()
+            .map(|p| p.parse::<usize>().ok()?.checked_sub(1))
+            .collect()
+    } else if parts.iter().all(|p| is_letters(p)) {
+        Some(
+            parts
+                .concat()
+                .chars()
+                .map(|c| (c.to_ascii_uppercase() as u8 - b'A') as usize)
+                .collect(),
+        )
+    } else {
+        None
+    };
+    positions
+        .filter(|ps| !ps.is_empty() && ps.len() <= nooptions && ps.iter().all(|p| *p < nooptions))
+}
+
+/// A single letter (`b`, `C`) or a run of capital letters in ascending order
+/// (`AC`); words such as `bad` or `None` are not option letters.
+fn is_letters(part: &str) -> bool {
+    match part.as_bytes() {
+        [c] => c.is_ascii_alphabetic(),
+        bytes => bytes.iter().all(u8::is_ascii_uppercase) && bytes.windows(2).all(|w| w[0] < w[1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn finds_named_columns() {
+        let header = row(&[
+            "Question", "Option A", "Option B", "Option C", "Answer", "Topic", "Points",
+        ]);
+        let columns = ColumnMapping::default().columns(&header).unwrap();
+        assert_eq!(columns.text, 0);
+        assert_eq!(columns.group, None);
+        assert_eq!(columns.options, Some(vec![1, 2, 3]));
+        assert_eq!(columns.answer, Some(4));
+        assert_eq!(
+            columns.metadata,
+            vec![(5, "Topic".to_string()), (6, "Points".to_string())]
+        );
+        assert_eq!(
+            ColumnMapping::default().columns(&row(&["1", "What?"])),
+            None
+        );
+    }
+
+    #[test]
+    fn custom_column_names() {
+        let mut mapping = ColumnMapping::default();
+        mapping.set("group=Topic").unwrap();
+        mapping.set("text=Stem|Prompt").unwrap();
+        assert!(mapping.set("points=Points").is_err());
+        let columns = mapping
+            .columns(&row(&["Topic", "Prompt", "Choice 1"]))
+            .unwrap();
+        assert_eq!((columns.group, columns.text), (Some(0), 1));
+        assert_eq!(columns.options, Some(vec![2]));
+    }
+
+    #[test]
+    fn resolves_answers() {
+        let options = row(&["4", "6", "7", "8"]);
+        let auto = |answer: &str| answer_positions(answer, &options, AnswerFormat::Auto).ok();
+        assert_eq!(auto("C"), Some(vec![2]));
+        assert_eq!(auto("a, c"), Some(vec![0, 2]));
+        assert_eq!(auto("AC"), Some(vec![0, 2]));
+        assert_eq!(auto("7"), Some(vec![2]));
+        assert_eq!(auto("1;2"), Some(vec![0, 1]));
+        assert_eq!(auto("E"), None);
+        assert_eq!(auto("0"), None);
+        assert_eq!(auto(""), None);
+        assert_eq!(auto("CA"), None);
+        assert_eq!(auto("None"), None);
+        assert_eq!(
+            answer_positions("bad", &options, AnswerFormat::Auto).unwrap_err(),
+            "which names no option"
+        );
+    }
+
+    #[test]
+    fn ambiguous_answers_need_a_format() {
+        let options = row(&["4", "6", "7", "8"]);
+        assert_eq!(
+            answer_positions("4", &options, AnswerFormat::Auto).unwrap_err(),
+            "which is option D by position but option A by text; set the answer format"
+        );
+        assert_eq!(
+            answer_positions("4", &options, AnswerFormat::Position),
+            Ok(vec![3])
+        );
+        assert_eq!(
+            answer_positions("4", &options, AnswerFormat::Text),
+            Ok(vec![0])
+        );
+
+        let options = row(&["0.5", "1", "2"]);
+        assert!(answer_positions("1", &options, AnswerFormat::Auto).is_err());
+        assert_eq!(
+            answer_positions("1", &options, AnswerFormat::Position),
+            Ok(vec![0])
+        );
+        assert_eq!(
+            answer_positions("1", &options, AnswerFormat::Text),
+            Ok(vec![1])
+        );
+        assert_eq!(
+            answer_positions("B", &options, AnswerFormat::Text).unwrap_err(),
+            "which names no option"
+        );
+    }
+}
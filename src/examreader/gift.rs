@@ -0,0 +1,427 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use super::moodle::{html_to_tex, tex_math};
+use super::{choices_from_marked_options, OptionMarks, ReadQuestions};
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    errors::ExamReaderError,
+    groups::category_group,
+    shuffler::Question,
+};
+
+/// Characters with a meaning in GIFT, written as `\~`, `\=`, ... in text.
+const GIFT_SPECIAL: [char; 6] = ['~', '=', '#', '{', '}', ':'];
+
+/// Reads the multiple choice and true/false questions of a GIFT file.
+///
+/// Choices starting with `=` are correct, as are `~` choices with a positive
+/// weight (`~%50%`); feedback after `#` is dropped. `$CATEGORY:` lines start
+/// a new `Question::group`, numbered as in [`from_moodle_xml`](super::from_moodle_xml).
+/// A missing word question keeps a blank in place of its answers.
+///
+/// Essay, short answer, numerical, matching and description items are
+/// skipped and reported as warnings; an unclosed answer block or a malformed
+/// choice fails the import with [`ExamReaderError::Diagnostics`].
+pub fn from_gift(filename: &str) -> Result<ReadQuestions, ExamReaderError> {
+    let content = fs::read_to_string(filename)?;
+    read_gift(filename, &content)
+}
+
+fn read_gift(file: &str, content: &str) -> Result<ReadQuestions, ExamReaderError> {
+    let mut qs: Vec<Question> = vec![];
+    let mut errors = vec![];
+    let mut warnings = vec![];
+    let mut groups: BTreeMap<String, u32> = BTreeMap::new();
+    let mut group = 1;
+    for (line, item) in gift_items(content) {
+        let at = |severity: Severity, message: String| {
+            Diagnostic::at_line(file, content, line, 1, severity, message)
+        };
+        if let Some(category) = item.strip_prefix("$CATEGORY:") {
+            group = category_group(category.trim(), &mut groups);
+            continue;
+        }
+        match parse_item(&item) {
+            Ok(GiftItem::Question { text, options }) => {
+                if options.iter().all(|(marks, _)| !marks.correct) {
+                    warnings.push(at(
+                        Severity::Warning,
+                        "question has no correct answer; the first one is taken".to_string(),
+                    ));
+                }
+                qs.push(Question {
+                    text,
+                    order: qs.len() as u32 + 1,
+                    choices: Some(choices_from_marked_options(options)),
+                    group,
+                    metadata: BTreeMap::new(),
+                });
+            }
+            Ok(GiftItem::Skipped(message)) => warnings.push(at(Severity::Warning, message)),
+            Err(message) => errors.push(at(Severity::Error, message)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ExamReaderError::Diagnostics(errors));
+    }
+    if qs.is_empty() {
+        return Err(ExamReaderError::TemplateError(
+            "no questions were found".to_string(),
+        ));
+    }
+    Ok((qs, warnings))
+}
+
+enum GiftItem {
+    Question {
+        text: String,
+        options: Vec<(OptionMarks, String)>,
+    },
+    /// The warning explaining why the item is skipped.
+    Skipped(String),
+}
+
+/// The items of a GIFT file with their first line (1-based). Items are
+/// separated by blank lines; comment lines are dropped and every
+/// `$CATEGORY:` line is an item of its own.
+fn gift_items(content: &str) -> Vec<(usize, String)> {
+    let mut items: Vec<(usize, String)> = vec![];
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("//") {
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with("$CATEGORY:") {
+            if let Some((start, lines)) = current.take() {
+                items.push((start, lines.join("\n")));
+            }
+            if !trimmed.is_empty() {
+                items.push((i + 1, trimmed.to_string()));
+            }
+            continue;
+        }
+        current.get_or_insert((i + 1, vec![])).1.push(line);
+    }
+    if let Some((start, lines)) = current {
+        items.push((start, lines.join("\n")));
+    }
+    items
+}
+
+fn parse_item(item: &str) -> Result<GiftItem, String> {
+    let mut rest = item.trim();
+    let mut name = String::new();
+    if let Some(title) = rest.strip_prefix("::") {
+        let end = find_unescaped(title, "::").ok_or("the title is never closed by `::`")?;
+        name = unescape(title[..end].trim());
+        rest = title[end + 2..].trim_start();
+    }
+    let mut format = "moodle";
+    if let Some(tagged) = rest.strip_prefix('[') {
+        if let Some(end) = tagged.find(']') {
+            format = &tagged[..end];
+            rest = &tagged[end + 1..];
+        }
+    }
+    let label = if name.is_empty() {
+        "question".to_string()
+    } else {
+        format!("question `{}`", name)
+    };
+    let skipped = |kind: &str| {
+        Ok(GiftItem::Skipped(format!(
+            "{} of type `{}` is not supported and is skipped",
+            label, kind
+        )))
+    };
+    let Some(open) = find_unescaped(rest, "{") else {
+        return skipped("description");
+    };
+    let close = find_unescaped(&rest[open..], "}")
+        .map(|end| open + end)
+        .ok_or("`{` is never closed by `}`")?;
+    let (before, answers, after) = (&rest[..open], &rest[open + 1..close], &rest[close + 1..]);
+    let text = if after.trim().is_empty() {
+        before.to_string()
+    } else {
+        format!(
+            "{} \\underline{{\\hspace{{2cm}}}} {}",
+            before.trim_end(),
+            after.trim_start()
+        )
+    };
+    let text = gift_text(&text, format);
+
+    let answers = answers.trim();
+    if answers.is_empty() {
+        return skipped("essay");
+    }
+    if answers.starts_with('#') {
+        return skipped("numerical");
+    }
+    let truth = answers[..find_unescaped(answers, "#").unwrap_or(answers.len())].trim();
+    if let Some(value) = match truth {
+        "T" | "TRUE" => Some(true),
+        "F" | "FALSE" => Some(false),
+        _ => None,
+    } {
+        let option = |text: &str, correct: bool| {
+            let marks = OptionMarks {
+                correct,
+                ..OptionMarks::default()
+            };
+            (marks, text.to_string())
+        };
+        return Ok(GiftItem::Question {
+            text,
+            options: vec![option("True", value), option("False", !value)],
+        });
+    }
+
+    let choices = split_choices(answers)?;
+    if choices
+        .iter()
+        .any(|(_, body)| find_unescaped(body, "->").is_some())
+    {
+        return skipped("matching");
+    }
+    if choices.iter().all(|(marker, _)| *marker == '=') {
+        return skipped("shortanswer");
+    }
+    let options = choices
+        .into_iter()
+        .map(|(marker, body)| {
+            let body = &body[..find_unescaped(body, "#").unwrap_or(body.len())];
+            let (weight, body) = match body.trim_start().strip_prefix('%') {
+                Some(weighted) => {
+                    let end = weighted.find('%').ok_or_else(|| {
+                        format!("the weight of `{}` is never closed", body.trim())
+                    })?;
+                    let weight: f64 = weighted[..end]
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("`{}` is not a weight", &weighted[..end]))?;
+                    (Some(weight), &weighted[end + 1..])
+                }
+                None => (None, body),
+            };
+            let correct = marker == '=' || weight.is_some_and(|w| w > 0.0);
+            let marks = OptionMarks {
+                correct,
+                ..OptionMarks::default()
+            };
+            Ok((marks, gift_text(body, format)))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(GiftItem::Question { text, options })
+}
+
+/// The `=` and `~` choices of an answer block with their bodies.
+fn split_choices(answers: &str) -> Result<Vec<(char, &str)>, String> {
+    let mut starts: Vec<(usize, char)> = vec![];
+    let mut escaped = false;
+    for (i, c) in answers.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == '~' {
+            starts.push((i, c));
+        }
+    }
+    let first = starts.first().map(|(i, _)| *i).unwrap_or(answers.len());
+    if !answers[..first].trim().is_empty() {
+        return Err(format!(
+            "choice `{}` must start with `=` or `~`",
+            answers[..first].trim()
+        ));
+    }
+    Ok(starts
+        .iter()
+        .enumerate()
+        .map(|(k, (i, marker))| {
+            let end = starts.get(k + 1).map(|(j, _)| *j).unwrap_or(answers.len());
+            (*marker, &answers[i + 1..end])
+        })
+        .collect())
+}
+
+/// Position of the first `pattern` not preceded by an escaping backslash.
+fn find_unescaped(text: &str, pattern: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[i..].starts_with(pattern) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Drops the backslash before a special character or another backslash;
+/// any other backslash is kept.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && (next == '\\' || GIFT_SPECIAL.contains(&next)) => {
+                out.push(next);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// TeX of a GIFT text written in `format`.
+fn gift_text(text: &str, format: &str) -> String {
+    let text = unescape(text);
+    if format == "html" {
+        return html_to_tex(&text);
+    }
+    tex_math(&text)
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examwriter::to_gift;
+    use crate::shuffler::{Choice, Choices, CorrectChoice, Exam};
+
+    #[test]
+    fn reads_gift_file() {
+        let (qs, warnings) = from_gift("files/testing/sample.gift").unwrap();
+        let read: Vec<(&str, u32, Vec<u32>, Vec<&str>)> = qs
+            .iter()
+            .map(|q| {
+                let cs = q.choices.as_ref().unwrap();
+                (
+                    q.text.as_str(),
+                    q.group,
+                    cs.correct_positions(),
+                    cs.0.iter().map(|c| c.text.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            read,
+            vec![
+                ("What is $1+1$?", 1, vec![1], vec!["1", "2", "3"]),
+                ("Which are prime?", 1, vec![0, 2], vec!["2", "4", "5"]),
+                ("The sky is green.", 2, vec![1], vec!["True", "False"]),
+                (
+                    "Ratio {a:b} is \\underline{\\hspace{2cm}} to 1.",
+                    2,
+                    vec![0],
+                    vec!["equal", "close"]
+                ),
+            ]
+        );
+        let found: Vec<(usize, &str)> = warnings
+            .iter()
+            .map(|w| (w.line, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    18,
+                    "question `Explain` of type `essay` is not supported and is skipped"
+                ),
+                (
+                    20,
+                    "question of type `matching` is not supported and is skipped"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_malformed_gift() {
+        let content = "Fine? {=yes ~no}\n\nBroken? {=yes ~no\n\nAlso? {yes =no}";
+        match read_gift("bad.gift", content).unwrap_err() {
+            ExamReaderError::Diagnostics(ds) => {
+                let found: Vec<(usize, &str)> =
+                    ds.iter().map(|d| (d.line, d.message.as_str())).collect();
+                assert_eq!(
+                    found,
+                    vec![
+                        (3, "`{` is never closed by `}`"),
+                        (5, "choice `yes` must start with `=` or `~`"),
+                    ]
+                );
+            }
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn round_trips_the_gift_writer() {
+        let mut q = Question::new(
+            "Is $\\frac{a}{b} = 1$?\nWhy: think.",
+            1,
+            Some(Choices(
+                vec![
+                    Choice::new("yes"),
+                    Choice::new("no ~ never"),
+                    Choice::new("#1"),
+                ],
+                CorrectChoice(1),
+                None,
+            )),
+        );
+        q.group = 3;
+        let ex = Exam {
+            questions: Some(vec![q.clone()]),
+            ..Exam::new("bank")
+        };
+        let (qs, warnings) = read_gift("bank.gift", &to_gift(&ex, &[])).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(qs[0].text, q.text);
+        assert_eq!(qs[0].group, 3);
+        let cs = qs[0].choices.as_ref().unwrap();
+        let texts: Vec<&str> = cs.0.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["yes", "no ~ never", "#1"]);
+        assert_eq!(cs.correct_positions(), vec![1]);
+    }
+
+    #[test]
+    fn round_trips_backslashes() {
+        let q = Question::new(
+            "Is $\\{1,2\\} = \\{2,1\\}$?\\\\ Think.",
+            1,
+            Some(Choices(
+                vec![Choice::new("$\\{1\\}$"), Choice::new("a \\\\ b")],
+                CorrectChoice(0),
+                None,
+            )),
+        );
+        let ex = Exam {
+            questions: Some(vec![q.clone()]),
+            ..Exam::new("bank")
+        };
+        let (qs, warnings) = read_gift("bank.gift", &to_gift(&ex, &[])).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(qs[0].text, q.text);
+        let cs = qs[0].choices.as_ref().unwrap();
+        let texts: Vec<&str> = cs.0.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["$\\{1\\}$", "a \\\\ b"]);
+    }
+
+    #[test]
+    fn unescapes_in_one_pass() {
+        assert_eq!(unescape("\\\\\\{ \\= \\x"), "\\{ = \\x");
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use roxmltree::{Document, Node};
+
+use super::{choices_from_marked_options, OptionMarks, ReadQuestions};
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    errors::ExamReaderError,
+    groups::category_group,
+    shuffler::Question,
+};
+
+/// Reads the `multichoice` and `truefalse` questions of a Moodle XML file.
+///
+/// A choice is correct when its fraction is 100, or positive in questions
+/// accepting several answers. Every category starts a new `Question::group`:
+/// `.../Group <n>`, as written by `examwriter::to_moodle_xml`, gives group
+/// `n` and other categories the next free number. The default grade is kept
+/// as the `Points` metadata.
+///
+/// Questions of other types (essay, matching, ...) are skipped and reported
+/// as warnings.
+pub fn from_moodle_xml(filename: &str) -> Result<ReadQuestions, ExamReaderError> {
+    let content = fs::read_to_string(filename)?;
+    read_moodle_xml(filename, &content)
+}
+
+fn read_moodle_xml(file: &str, content: &str) -> Result<ReadQuestions, ExamReaderError> {
+    let doc = Document::parse(content).map_err(|err| {
+        let pos = err.pos();
+        ExamReaderError::Diagnostics(vec![Diagnostic::at_line(
+            file,
+            content,
+            pos.row as usize,
+            pos.col as usize,
+            Severity::Error,
+            err.to_string(),
+        )])
+    })?;
+    let warning = |node: Node, message: String| {
+        let pos = doc.text_pos_at(node.range().start);
+        Diagnostic::at_line(
+            file,
+            content,
+            pos.row as usize,
+            pos.col as usize,
+            Severity::Warning,
+            message,
+        )
+    };
+
+    let mut qs: Vec<Question> = vec![];
+    let mut warnings = vec![];
+    let mut groups: BTreeMap<String, u32> = BTreeMap::new();
+    let mut group = 1;
+    for node in doc
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("question"))
+    {
+        let kind = node.attribute("type").unwrap_or_default();
+        let name = child_text(node, "name").unwrap_or_default();
+        match kind {
+            "category" => {
+                let category = child_text(node, "category").unwrap_or_default();
+                group = category_group(&category, &mut groups);
+            }
+            "multichoice" | "truefalse" => {
+                let single = kind == "truefalse"
+                    || child_text(node, "single").is_none_or(|s| s.trim() != "false");
+                let options: Vec<(OptionMarks, String)> = node
+                    .children()
+                    .filter(|n| n.has_tag_name("answer"))
+                    .map(|answer| {
+                        let fraction: f64 = answer
+                            .attribute("fraction")
+                            .and_then(|f| f.trim().parse().ok())
+                            .unwrap_or(0.0);
+                        let correct = if single {
+                            fraction >= 100.0
+                        } else {
+                            fraction > 0.0
+                        };
+                        let marks = OptionMarks {
+                            correct,
+                            ..OptionMarks::default()
+                        };
+                        (
+                            marks,
+                            html_to_tex(&child_text(answer, "text").unwrap_or_default()),
+                        )
+                    })
+                    .collect();
+                if options.is_empty() {
+                    warnings.push(warning(
+                        node,
+                        format!("question `{}` has no answers and is skipped", name),
+                    ));
+                    continue;
+                }
+                if options.iter().all(|(marks, _)| !marks.correct) {
+                    warnings.push(warning(
+                        node,
+                        format!(
+                            "question `{}` has no correct answer; the first one is taken",
+                            name
+                        ),
+                    ));
+                }
+                let mut metadata = BTreeMap::new();
+                if let Some(grade) =
+                    child_text(node, "defaultgrade").and_then(|g| g.trim().parse::<f64>().ok())
+                {
+                    metadata.insert("Points".to_string(), grade.to_string());
+                }
+                qs.push(Question {
+                    text: html_to_tex(&child_text(node, "questiontext").unwrap_or_default()),
+                    order: qs.len() as u32 + 1,
+                    choices: Some(choices_from_marked_options(options)),
+                    group,
+                    metadata,
+                });
+            }
+            kind => warnings.push(warning(
+                node,
+                format!(
+                    "question `{}` of type `{}` is not supported and is skipped",
+                    name, kind
+                ),
+            )),
+        }
+    }
+    if qs.is_empty() {
+        return Err(ExamReaderError::TemplateError(
+            "no questions were found".to_string(),
+        ));
+    }
+    Ok((qs, warnings))
+}
+
+/// The text of the `<text>` element inside the child `tag` of `node`, or of
+/// the child itself when it holds no `<text>`.
+fn child_text(node: Node, tag: &str) -> Option<String> {
+    let child = node.children().find(|n| n.has_tag_name(tag))?;
+    let text_node = child
+        .children()
+        .find(|n| n.has_tag_name("text"))
+        .unwrap_or(child);
+    Some(
+        text_node
+            .descendants()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect(),
+    )
+}
+
+/// Turns Moodle HTML back into TeX: tags are dropped (`<br>` and paragraph
+/// ends become new lines), entities decoded and the math delimiters replaced
+/// by [`tex_math`].
+pub(super) fn html_to_tex(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => {
+                let tag = rest[start + 1..start + end].trim().to_lowercase();
+                if tag.starts_with("br") || tag == "/p" || tag == "/div" {
+                    text.push('\n');
+                }
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    text.push_str(rest);
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    tex_math(&text)
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Replaces the MathJax delimiters `\(...\)` and `\[...\]` by `$...$` and
+/// `$$...$$`.
+pub(super) fn tex_math(text: &str) -> String {
+    text.replace("\\(", "$")
+        .replace("\\)", "$")
+        .replace("\\[", "$$")
+        .replace("\\]", "$$")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examwriter::to_moodle_xml;
+    use crate::shuffler::{Choice, Choices, CorrectChoice, Exam};
+
+    #[test]
+    fn reads_moodle_xml_file() {
+        let (qs, warnings) = from_moodle_xml("files/testing/moodle.xml").unwrap();
+        let read: Vec<(&str, u32, Vec<u32>, usize)> = qs
+            .iter()
+            .map(|q| {
+                let cs = q.choices.as_ref().unwrap();
+                (q.text.as_str(), q.group, cs.correct_positions(), cs.0.len())
+            })
+            .collect();
+        assert_eq!(
+            read,
+            vec![
+                ("What is $1+1$?", 1, vec![1], 3),
+                ("Which are prime?\nPick all.", 1, vec![0, 2], 3),
+                ("The sky is green.", 2, vec![1], 2),
+            ]
+        );
+        assert_eq!(qs[0].metadata.get("Points"), Some(&"2".to_string()));
+        let found: Vec<(usize, &str)> = warnings
+            .iter()
+            .map(|w| (w.line, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    32,
+                    "question `Explain` of type `essay` is not supported and is skipped"
+                ),
+                (
+                    36,
+                    "question `Match` of type `matching` is not supported and is skipped"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_malformed_xml() {
+        let err = read_moodle_xml("bad.xml", "<quiz>\n  <question>\n</quiz>").unwrap_err();
+        match err {
+            ExamReaderError::Diagnostics(ds) => assert_eq!(ds[0].line, 3),
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn round_trips_the_moodle_writer() {
+        let mut q = Question::new(
+            "Is $a < b$ &amp; $b < c$?",
+            1,
+            Some(Choices(
+                vec![Choice::new("yes"), Choice::new("$x^2$")],
+                CorrectChoice(1),
+                None,
+            )),
+        );
+        q.group = 3;
+        let ex = Exam {
+            questions: Some(vec![q]),
+            ..Exam::new("bank")
+        };
+        let (qs, warnings) = read_moodle_xml("bank.xml", &to_moodle_xml(&ex, &[])).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(qs[0].text, "Is $a < b$ &amp; $b < c$?");
+        assert_eq!(qs[0].group, 3);
+        assert_eq!(qs[0].choices.as_ref().unwrap().1, CorrectChoice(1));
+        assert_eq!(qs[0].choices.as_ref().unwrap().0[1].text, "$x^2$");
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+use zip::ZipArchive;
+
+use super::moodle::html_to_tex;
+use super::{choices_from_marked_options, OptionMarks, ReadQuestions};
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    errors::ExamReaderError,
+    examwriter::qti::kebab_case,
+    shuffler::Question,
+};
+
+/// The XML files of a package by their path in it, separated by `/`.
+type Package = BTreeMap<String, String>;
+
+/// Reads the `choiceInteraction` items of a QTI 2.1 package, given as a zip
+/// file or as the directory it was unpacked to. QTI 3.0 names are read too.
+///
+/// Items come in the order of the assessment test of the package, every
+/// section holding items giving a `Question::group`; without a test, in the
+/// order of the manifest, and without a manifest, by file name. The correct
+/// choices are those of the correct response or, when there is none, those
+/// mapped to a positive score. Choices with `fixed="true"` are pinned.
+///
+/// Items with other interactions (text entry, matching, ...) are skipped and
+/// reported as warnings.
+pub fn from_qti(path: &str) -> Result<ReadQuestions, ExamReaderError> {
+    let package = read_package(path)?;
+    read_qti(path, &package)
+}
+
+fn read_package(path: &str) -> Result<Package, ExamReaderError> {
+    let mut files = Package::new();
+    if Path::new(path).is_dir() {
+        collect_dir(Path::new(path), "", &mut files)?;
+        return Ok(files);
+    }
+    let package_error = |err: zip::result::ZipError| ExamReaderError::PackageError(err.to_string());
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(package_error)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(package_error)?;
+        if entry.is_file() && entry.name().to_lowercase().ends_with(".xml") {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            files.insert(entry.name().to_string(), content);
+        }
+    }
+    Ok(files)
+}
+
+fn collect_dir(dir: &Path, prefix: &str, files: &mut Package) -> Result<(), ExamReaderError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.path().is_dir() {
+            collect_dir(&entry.path(), &format!("{}/", name), files)?;
+        } else if name.to_lowercase().ends_with(".xml") {
+            files.insert(name, fs::read_to_string(entry.path())?);
+        }
+    }
+    Ok(())
+}
+
+fn read_qti(path: &str, package: &Package) -> Result<ReadQuestions, ExamReaderError> {
+    let mut qs: Vec<Question> = vec![];
+    let mut warnings = vec![];
+    for (item, group) in package_items(path, package)? {
+        let content = package.get(&item).ok_or_else(|| missing(&item))?;
+        let (question, found) = read_item(&format!("{}[{}]", path, item), content)?;
+        warnings.extend(found);
+        if let Some(question) = question {
+            qs.push(Question {
+                order: qs.len() as u32 + 1,
+                group,
+                ..question
+            });
+        }
+    }
+    if qs.is_empty() {
+        return Err(ExamReaderError::TemplateError(
+            "no questions were found".to_string(),
+        ));
+    }
+    Ok((qs, warnings))
+}
+
+/// The items of `package` in order, with their group.
+fn package_items(path: &str, package: &Package) -> Result<Vec<(String, u32)>, ExamReaderError> {
+    let manifest = package
+        .iter()
+        .filter(|(p, _)| p.rsplit('/').next() == Some("imsmanifest.xml"))
+        .min_by_key(|(p, _)| p.len());
+    let Some((manifest_path, manifest)) = manifest else {
+        return Ok(package
+            .iter()
+            .filter(|(_, content)| {
+                Document::parse(content)
+                    .is_ok_and(|doc| is_qti(doc.root_element(), "assessmentItem"))
+            })
+            .map(|(p, _)| (p.clone(), 1))
+            .collect());
+    };
+    let doc = parse(&format!("{}[{}]", path, manifest_path), manifest)?;
+    let resources: Vec<(&str, String)> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("resource"))
+        .filter_map(|n| {
+            let href = n.attribute("href")?;
+            Some((
+                n.attribute("type").unwrap_or_default(),
+                join(parent(manifest_path), href),
+            ))
+        })
+        .collect();
+    let Some((_, test_path)) = resources
+        .iter()
+        .find(|(kind, _)| kind.starts_with("imsqti_test"))
+    else {
+        return Ok(resources
+            .into_iter()
+            .filter(|(kind, _)| kind.starts_with("imsqti_item"))
+            .map(|(_, item)| (item, 1))
+            .collect());
+    };
+    let test = package.get(test_path).ok_or_else(|| missing(test_path))?;
+    let doc = parse(&format!("{}[{}]", path, test_path), test)?;
+    let is_ref = |n: &Node| is_qti(*n, "assessmentItemRef");
+    let sections: Vec<Node> = doc
+        .descendants()
+        .filter(|n| is_qti(*n, "assessmentSection") && n.children().any(|c| is_ref(&c)))
+        .collect();
+    Ok(doc
+        .descendants()
+        .filter(is_ref)
+        .filter_map(|n| {
+            let href = n.attribute("href")?;
+            let section = n.parent()?;
+            let group = sections.iter().position(|s| *s == section).unwrap_or(0) as u32 + 1;
+            Some((join(parent(test_path), href), group))
+        })
+        .collect())
+}
+
+/// The question of an item, or `None` when the item is skipped, with the
+/// warnings about it.
+fn read_item(
+    file: &str,
+    content: &str,
+) -> Result<(Option<Question>, Vec<Diagnostic>), ExamReaderError> {
+    let doc = parse(file, content)?;
+    let warning = |node: Node, message: String| {
+        let pos = doc.text_pos_at(node.range().start);
+        Diagnostic::at_line(
+            file,
+            content,
+            pos.row as usize,
+            pos.col as usize,
+            Severity::Warning,
+            message,
+        )
+    };
+    let root = doc.root_element();
+    let name = root.attribute("identifier").unwrap_or_default();
+    let skipped = |node: Node, reason: String| {
+        Ok((
+            None,
+            vec![warning(
+                node,
+                format!("item `{}` {} and is skipped", name, reason),
+            )],
+        ))
+    };
+    let Some(body) = root.children().find(|n| is_qti(*n, "itemBody")) else {
+        return skipped(root, "has no item body".to_string());
+    };
+    let interactions: Vec<Node> = body
+        .descendants()
+        .filter(|n| {
+            let tag = n.tag_name().name();
+            n.is_element() && (tag.ends_with("Interaction") || tag.ends_with("-interaction"))
+        })
+        .collect();
+    let interaction = match interactions[..] {
+        [interaction] if is_qti(interaction, "choiceInteraction") => interaction,
+        [interaction] => {
+            return skipped(
+                interaction,
+                format!("has an unsupported `{}`", interaction.tag_name().name()),
+            )
+        }
+        [] => return skipped(body, "has no interaction".to_string()),
+        _ => return skipped(body, "has several interactions".to_string()),
+    };
+
+    let response = qti_attribute(interaction, "responseIdentifier");
+    let declaration = root
+        .children()
+        .find(|n| is_qti(*n, "responseDeclaration") && n.attribute("identifier") == response);
+    let correct: Vec<&str> = declaration
+        .map(|d| {
+            let values: Vec<&str> = d
+                .children()
+                .filter(|n| is_qti(*n, "correctResponse"))
+                .flat_map(|n| n.children().filter(|v| is_qti(*v, "value")))
+                .filter_map(|v| v.text())
+                .map(|v| v.trim())
+                .collect();
+            if !values.is_empty() {
+                return values;
+            }
+            d.descendants()
+                .filter(|n| is_qti(*n, "mapEntry"))
+                .filter(|n| {
+                    qti_attribute(*n, "mappedValue")
+                        .and_then(|v| v.trim().parse::<f64>().ok())
+                        .is_some_and(|v| v > 0.0)
+                })
+                .filter_map(|n| qti_attribute(n, "mapKey"))
+                .collect()
+        })
+        .unwrap_or_default();
+    let options: Vec<(OptionMarks, String)> = interaction
+        .children()
+        .filter(|n| is_qti(*n, "simpleChoice"))
+        .map(|c| {
+            let identifier = c.attribute("identifier").unwrap_or_default();
+            let marks = OptionMarks {
+                pinned: c.attribute("fixed") == Some("true"),
+                correct: correct.contains(&identifier),
+            };
+            (marks, html_to_tex(&content[c.range()]))
+        })
+        .collect();
+    if options.is_empty() {
+        return skipped(interaction, "has no choices".to_string());
+    }
+    let mut warnings = vec![];
+    if options.iter().all(|(marks, _)| !marks.correct) {
+        warnings.push(warning(
+            interaction,
+            format!(
+                "item `{}` has no correct response; the first choice is taken",
+                name
+            ),
+        ));
+    }
+
+    let prompt = interaction
+        .children()
+        .find(|n| is_qti(*n, "prompt"))
+        .map(|p| &content[p.range()])
+        .unwrap_or_default();
+    let (body, interaction) = (body.range(), interaction.range());
+    let text = html_to_tex(&format!(
+        "{}<br/>{}<br/>{}",
+        &content[body.start..interaction.start],
+        prompt,
+        &content[interaction.end..body.end]
+    ));
+    let question = Question {
+        text,
+        order: 0,
+        choices: Some(choices_from_marked_options(options)),
+        group: 1,
+        metadata: BTreeMap::new(),
+    };
+    Ok((Some(question), warnings))
+}
+
+/// Whether `node` is the QTI element `name`, given by its QTI 2.1 name.
+fn is_qti(node: Node, name: &str) -> bool {
+    let tag = node.tag_name().name();
+    node.is_element() && (tag == name || tag.strip_prefix("qti-") == Some(&kebab_case(name)))
+}
+
+/// The QTI attribute `name` of `node`, given by its QTI 2.1 name.
+fn qti_attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute(name)
+        .or_else(|| node.attribute(kebab_case(name).as_str()))
+}
+
+fn parse<'a>(file: &str, content: &'a str) -> Result<Document<'a>, ExamReaderError> {
+    Document::parse(content).map_err(|err| {
+        let pos = err.pos();
+        ExamReaderError::Diagnostics(vec![Diagnostic::at_line(
+            file,
+            content,
+            pos.row as usize,
+            pos.col as usize,
+            Severity::Error,
+            err.to_string(),
+        )])
+    })
+}
+
+fn missing(path: &str) -> ExamReaderError {
+    ExamReaderError::PackageError(format!("`{}` is listed but missing", path))
+}
+
+/// The directory of a path in the package, empty at the top.
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .unwrap_or_default()
+}
+
+/// `href` relative to the package directory `dir`.
+fn join(dir: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examwriter::{qti_files, to_qti_package, QtiVersion};
+    use crate::shuffler::{Choice, ChoiceOrdering, Choices, CorrectChoice, Exam};
+
+    fn exam() -> Exam {
+        Exam {
+            questions: Some(vec![
+                Question::new(
+                    "Is $a < b$?\n\nSure?",
+                    1,
+                    Some(Choices(
+                        vec![
+                            Choice::new("yes"),
+                            Choice::new("no"),
+                            Choice::pinned("maybe"),
+                        ],
+                        CorrectChoice(1),
+                        Some(ChoiceOrdering(vec![1, 0, 2])),
+                    )),
+                ),
+                Question::new("Explain.", 2, None),
+            ]),
+            ..Exam::new("bank")
+        }
+    }
+
+    #[test]
+    fn reads_qti_directory() {
+        let (qs, warnings) = from_qti("files/testing/qti").unwrap();
+        type Read<'a> = (&'a str, u32, Vec<u32>, Vec<(&'a str, bool)>);
+        let read: Vec<Read> = qs
+            .iter()
+            .map(|q| {
+                let cs = q.choices.as_ref().unwrap();
+                (
+                    q.text.as_str(),
+                    q.group,
+                    cs.correct_positions(),
+                    cs.0.iter().map(|c| (c.text.as_str(), c.pinned)).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            read,
+            vec![
+                (
+                    "What is $1+1$?\nPick one.",
+                    1,
+                    vec![1],
+                    vec![("1", false), ("2", false), ("None of these", true)]
+                ),
+                (
+                    "Which are prime?",
+                    2,
+                    vec![0, 2],
+                    vec![("2", false), ("4", false), ("5", false)]
+                ),
+            ]
+        );
+        let found: Vec<(usize, &str)> = warnings
+            .iter()
+            .map(|w| (w.line, w.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(
+                5,
+                "item `capital` has an unsupported `textEntryInteraction` and is skipped"
+            )]
+        );
+        assert_eq!(
+            warnings[0].file,
+            "files/testing/qti[items/capital.xml]".to_string()
+        );
+    }
+
+    #[test]
+    fn round_trips_the_qti_writer() {
+        for version in [QtiVersion::V2_1, QtiVersion::V3_0] {
+            let package: Package = qti_files(&exam(), version).into_iter().collect();
+            let (qs, warnings) = read_qti("bank", &package).unwrap();
+            assert_eq!(qs.len(), 1);
+            assert_eq!(qs[0].text, "Is $a < b$?\nSure?");
+            let cs = qs[0].choices.as_ref().unwrap();
+            let texts: Vec<&str> = cs.0.iter().map(|c| c.text.as_str()).collect();
+            assert_eq!(texts, vec!["no", "yes", "maybe"]);
+            assert_eq!(cs.correct_positions(), vec![1]);
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0].message.contains("has an unsupported"));
+        }
+    }
+
+    #[test]
+    fn reads_qti_zip() {
+        let path = std::env::temp_dir().join("mc-exam-randomizer-qti-test.zip");
+        fs::write(&path, to_qti_package(&exam(), QtiVersion::V2_1).unwrap()).unwrap();
+        let (qs, _) = from_qti(&path.to_string_lossy()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(qs[0].text, "Is $a < b$?\nSure?");
+    }
+
+    #[test]
+    fn joins_package_paths() {
+        assert_eq!(join("tests", "../items/a.xml"), "items/a.xml");
+        assert_eq!(join("", "./items/a.xml"), "items/a.xml");
+    }
+}
@@ -0,0 +1,20 @@
+pub mod aiken;
+pub mod delimited;
+pub mod gift;
+pub mod key;
+pub mod moodle;
+pub mod qti;
+pub mod tex;
+
+pub use aiken::*;
+pub use delimited::*;
+pub use gift::*;
+pub use key::*;
+pub use moodle::*;
+pub use qti::*;
+pub use tex::*;
+
+/// Letter of the choice printed at `index` (0-based): `A`, `B`, ...
+pub fn choice_letter(index: u32) -> char {
+    char::from_u32('A' as u32 + index).unwrap_or('?')
+}
@@ -0,0 +1,113 @@
+use std::fs;
+
+use super::choice_letter;
+use crate::{
+    errors::ExamWriterError,
+    shuffler::{strip_variables, Exam},
+};
+
+/// Writes `ex` in the Aiken format read by `examreader::from_aiken`, the
+/// choices in printed order.
+///
+/// Aiken has one line per question and option, so new lines are replaced by
+/// spaces. Questions without choices, without exactly one correct choice or with
+/// more than 26 choices cannot be written.
+pub fn to_aiken(ex: &Exam) -> Result<String, ExamWriterError> {
+    let mut out = String::new();
+    for q in ex.ordered_questions() {
+        let unsupported = |reason: &str| ExamWriterError::Unsupported {
+            question: q.order,
+            format: "Aiken".to_string(),
+            reason: reason.to_string(),
+        };
+        let cs = q
+            .choices
+            .as_ref()
+            .filter(|cs| !cs.0.is_empty())
+            .ok_or_else(|| unsupported("it has no choices"))?;
+        let correct = match cs.correct_positions()[..] {
+            [correct] => correct,
+            [] => return Err(unsupported("it has no correct choice")),
+            _ => return Err(unsupported("it has several correct choices")),
+        };
+        if cs.0.len() > 26 {
+            return Err(unsupported("it has more than 26 choices"));
+        }
+        out.push_str(&one_line(&strip_variables(&q.text)));
+        out.push('\n');
+        cs.ordered().into_iter().enumerate().for_each(|(i, c)| {
+            out.push_str(&format!(
+                "{}. {}\n",
+                choice_letter(i as u32),
+                one_line(&c.text)
+            ));
+        });
+        out.push_str(&format!("ANSWER: {}\n\n", choice_letter(correct)));
+    }
+    Ok(out)
+}
+
+pub fn write_aiken(filename: &str, ex: &Exam) -> Result<(), ExamWriterError> {
+    if ex.ordered_questions().is_empty() {
+        return Err(ExamWriterError::NoQuestions);
+    }
+    fs::write(filename, to_aiken(ex)?)?;
+    Ok(())
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{Choice, ChoiceOrdering, Choices, CorrectChoice, Question};
+
+    #[test]
+    fn aiken_keeps_printed_order() {
+        let ex = Exam {
+            questions: Some(vec![Question::new(
+                "Which one\nreally?",
+                1,
+                Some(Choices(
+                    vec![Choice::new("a"), Choice::new("b"), Choice::new("c")],
+                    CorrectChoice(1),
+                    Some(ChoiceOrdering(vec![2, 0, 1])),
+                )),
+            )]),
+            ..Exam::new("v1")
+        };
+        assert_eq!(
+            to_aiken(&ex).unwrap(),
+            "Which one really?\nA. c\nB. a\nC. b\nANSWER: B\n\n"
+        );
+    }
+
+    #[test]
+    fn aiken_rejects_questions_without_single_answer() {
+        let ex = Exam {
+            questions: Some(vec![Question::new("Explain.", 1, None)]),
+            ..Exam::new("v1")
+        };
+        assert_eq!(
+            to_aiken(&ex).unwrap_err().to_string(),
+            "question 1 cannot be written as Aiken: it has no choices"
+        );
+        let reason = |choices: Choices| {
+            let ex = Exam {
+                questions: Some(vec![Question::new("Which?", 1, Some(choices))]),
+                ..Exam::new("v1")
+            };
+            to_aiken(&ex).unwrap_err().to_string()
+        };
+        assert!(reason(Choices(vec![], CorrectChoice(0), None)).ends_with("it has no choices"));
+        let two = vec![Choice::new("a"), Choice::new("b")];
+        assert!(reason(Choices(two.clone(), CorrectChoice(2), None))
+            .ends_with("it has no correct choice"));
+        let mut several = two;
+        several[1].correct = true;
+        assert!(reason(Choices(several, CorrectChoice(0), None))
+            .ends_with("it has several correct choices"));
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+
+use crate::{
+    constants::{OPTION_CORRECT_MARK, OPTION_PIN_MARK},
+    errors::ExamWriterError,
+    shuffler::Exam,
+};
+
+/// Writes `ex` in the layout read by `examreader::from_csv`: group, question
+/// text and then the options, one question per row.
+///
+/// The correct choice is prefixed with `*` and pinned choices with `!`.
+pub fn to_csv(ex: &Exam) -> Result<String, ExamWriterError> {
+    to_delimited(ex, b',')
+}
+
+/// Same as [`to_csv`] with tabs, as read by `examreader::from_txt`.
+pub fn to_txt(ex: &Exam) -> Result<String, ExamWriterError> {
+    to_delimited(ex, b'\t')
+}
+
+pub fn write_csv(filename: &str, ex: &Exam) -> Result<(), ExamWriterError> {
+    fs::write(filename, to_csv(ex)?)?;
+    Ok(())
+}
+
+pub fn write_txt(filename: &str, ex: &Exam) -> Result<(), ExamWriterError> {
+    fs::write(filename, to_txt(ex)?)?;
+    Ok(())
+}
+
+fn to_delimited(ex: &Exam, delimiter: u8) -> Result<String, ExamWriterError> {
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_writer(vec![]);
+    for q in ex.ordered_questions() {
+        let mut record = vec![q.group.to_string(), q.text.to_owned()];
+        if let Some(cs) = &q.choices {
+            let correct_positions = cs.correct_positions();
+            let options = cs.ordered().into_iter().enumerate().map(|(i, c)| {
+                let correct = if correct_positions.contains(&(i as u32)) {
+                    OPTION_CORRECT_MARK
+                } else {
+                    ""
+                };
+                let pinned = if c.pinned { OPTION_PIN_MARK } else { "" };
+                format!("{}{}{}", correct, pinned, c.text)
+            });
+            record.extend(options);
+        }
+        wtr.write_record(&record)?;
+    }
+    let bytes = wtr
+        .into_inner()
+        .map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{Choice, ChoiceOrdering, Choices, CorrectChoice, Question};
+
+    #[test]
+    fn csv_marks_correct_and_pinned_choices() {
+        let ex = Exam {
+            name: "v1".to_string(),
+            preamble: None,
+            questions: Some(vec![Question::new(
+                "Which one, \"really\"?",
+                1,
+                Some(Choices(
+                    vec![Choice::new("a"), Choice::new("b"), Choice::pinned("none")],
+                    CorrectChoice(1),
+                    Some(ChoiceOrdering(vec![1, 0, 2])),
+                )),
+            )]),
+            ordering: None,
+        };
+        assert_eq!(
+            to_csv(&ex).unwrap(),
+            "1,\"Which one, \"\"really\"\"?\",b,*a,!none\n"
+        );
+        assert_eq!(
+            to_txt(&ex).unwrap(),
+            "1\t\"Which one, \"\"really\"\"?\"\tb\t*a\t!none\n"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use super::moodle::{convert_math, fraction_text};
+use crate::{
+    errors::ExamWriterError,
+    shuffler::{strip_variables, version_code, Choices, Exam, Question},
+};
+
+/// Renders GIFT with the same categories as [`to_moodle_xml`](super::to_moodle_xml):
+/// `<master name>/Group <n>` for `master` and `CODE01/Group <n>`, ... for the
+/// versions. Questions without choices become essay questions.
+///
+/// A single correct choice is written as `=choice`; when there are several,
+/// they share a weight of 100% and every wrong choice takes away the same
+/// share. Choices are listed in printed order.
+pub fn to_gift(master: &Exam, versions: &[Exam]) -> String {
+    let mut doc = exam_questions(master, &master.name);
+    versions.iter().enumerate().for_each(|(i, version)| {
+        doc.push_str(&exam_questions(version, &version_code(i)));
+    });
+    doc
+}
+
+pub fn write_gift(filename: &str, master: &Exam, versions: &[Exam]) -> Result<(), ExamWriterError> {
+    if master.ordered_questions().is_empty() {
+        return Err(ExamWriterError::NoQuestions);
+    }
+    fs::write(filename, to_gift(master, versions))?;
+    Ok(())
+}
+
+fn exam_questions(ex: &Exam, category: &str) -> String {
+    let mut groups: BTreeMap<u32, Vec<&Question>> = BTreeMap::new();
+    ex.ordered_questions()
+        .into_iter()
+        .for_each(|q| groups.entry(q.group).or_default().push(q));
+    let mut out = String::new();
+    for (group, qs) in groups {
+        out.push_str(&format!(
+            "$CATEGORY: $course$/{}/Group {}\n\n",
+            category, group
+        ));
+        for q in qs {
+            out.push_str(&format!(
+                "::{} {}::{} {{",
+                escape(&ex.name),
+                q.order,
+                gift_text(&strip_variables(&q.text))
+            ));
+            if let Some(cs) = &q.choices {
+                out.push('\n');
+                out.push_str(&choices_gift(cs));
+            }
+            out.push_str("}\n\n");
+        }
+    }
+    out
+}
+
+fn choices_gift(cs: &Choices) -> String {
+    let correct = cs.correct_positions();
+    let share = 100.0 / correct.len() as f64;
+    cs.ordered()
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let is_correct = correct.contains(&(i as u32));
+            let mark = match (is_correct, correct.len()) {
+                (true, 1) => "=".to_string(),
+                (false, 1) => "~".to_string(),
+                (true, _) => format!("~%{}%", fraction_text(share)),
+                (false, _) => format!("~%{}%", fraction_text(-share)),
+            };
+            format!("    {}{}\n", mark, gift_text(&c.text))
+        })
+        .collect()
+}
+
+/// `tex` with the math delimiters converted for MathJax, the GIFT special
+/// characters escaped and no blank lines, which would end the question.
+fn gift_text(tex: &str) -> String {
+    convert_math(tex)
+        .lines()
+        .map(|l| escape(l.trim()))
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut out, c| {
+        if matches!(c, '~' | '=' | '#' | '{' | '}' | ':' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+        out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{Choice, CorrectChoice};
+
+    #[test]
+    fn gift_questions_and_categories() {
+        let mut second = Question::new(
+            "Which are even?",
+            2,
+            Some(Choices(
+                vec![
+                    Choice::new("2"),
+                    Choice::new("3"),
+                    Choice {
+                        correct: true,
+                        ..Choice::new("4")
+                    },
+                ],
+                CorrectChoice(0),
+                None,
+            )),
+        );
+        second.group = 2;
+        let ex = Exam {
+            questions: Some(vec![
+                Question::new(
+                    "Is $\\frac{1}{2} = 0.5$?",
+                    1,
+                    Some(Choices(
+                        vec![Choice::new("yes"), Choice::new("no")],
+                        CorrectChoice(0),
+                        None,
+                    )),
+                ),
+                second,
+                Question::new("Explain.", 3, None),
+            ]),
+            ..Exam::new("Major 1")
+        };
+        assert_eq!(
+            to_gift(&ex, &[]),
+            "$CATEGORY: $course$/Major 1/Group 1\n\n\
+             ::Major 1 1::Is \\\\(\\\\frac\\{1\\}\\{2\\} \\= 0.5\\\\)? {\n    =yes\n    ~no\n}\n\n\
+             ::Major 1 3::Explain. {}\n\n\
+             $CATEGORY: $course$/Major 1/Group 2\n\n\
+             ::Major 1 2::Which are even? {\n    ~%50%2\n    ~%-50%3\n    ~%50%4\n}\n\n"
+        );
+    }
+}
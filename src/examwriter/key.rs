@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::{
+    errors::ExamWriterError,
+    shuffler::{version_code, Exam, Scoring},
+};
+
+use super::choice_letter;
+
+/// The correct answers of one printed version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnswerKey {
+    pub version: String,
+    pub answers: Vec<KeyEntry>,
+}
+
+/// The answer of the question printed at position `question` (1-based).
+///
+/// `master_question` is the number of the same question in the master exam.
+/// `answer` holds one letter per correct choice (e.g. `B` or `AC`) and is
+/// empty when the question has no choices.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyEntry {
+    pub question: u32,
+    pub master_question: u32,
+    pub answer: String,
+}
+
+impl AnswerKey {
+    pub fn from_exam(version: &str, ex: &Exam) -> Self {
+        let answers = ex
+            .ordered_questions()
+            .into_iter()
+            .enumerate()
+            .map(|(i, q)| {
+                let answer = match &q.choices {
+                    Some(cs) if !cs.0.is_empty() => cs
+                        .correct_positions()
+                        .into_iter()
+                        .map(choice_letter)
+                        .collect(),
+                    _ => String::new(),
+                };
+                KeyEntry {
+                    question: i as u32 + 1,
+                    master_question: q.order,
+                    answer,
+                }
+            })
+            .collect();
+        AnswerKey {
+            version: version.to_string(),
+            answers,
+        }
+    }
+}
+
+impl KeyEntry {
+    /// Score of a response given as letters, e.g. `"AC"`.
+    pub fn score(&self, response: &str, scoring: Scoring) -> f64 {
+        scoring.score(&letter_positions(&self.answer), &letter_positions(response))
+    }
+}
+
+impl AnswerKey {
+    /// Total score of `responses`, one per printed question.
+    pub fn score(&self, responses: &[&str], scoring: Scoring) -> f64 {
+        self.answers
+            .iter()
+            .zip(responses)
+            .map(|(entry, response)| entry.score(response, scoring))
+            .sum()
+    }
+}
+
+fn letter_positions(letters: &str) -> Vec<u32> {
+    letters
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase() as u32 - 'A' as u32)
+        .collect()
+}
+
+/// Answer keys of `versions`, labelled with the same codes as the printed
+/// versions (`CODE01`, `CODE02`, ...).
+pub fn answer_keys(versions: &[Exam]) -> Vec<AnswerKey> {
+    versions
+        .iter()
+        .enumerate()
+        .map(|(i, ex)| AnswerKey::from_exam(&version_code(i), ex))
+        .collect()
+}
+
+/// One row per version and question: `version,question,answer,master_question`.
+pub fn keys_to_csv(keys: &[AnswerKey]) -> Result<String, ExamWriterError> {
+    let mut wtr = csv::Writer::from_writer(vec![]);
+    wtr.write_record(["version", "question", "answer", "master_question"])?;
+    for key in keys {
+        for entry in &key.answers {
+            wtr.write_record([
+                key.version.to_owned(),
+                entry.question.to_string(),
+                entry.answer.to_owned(),
+                entry.master_question.to_string(),
+            ])?;
+        }
+    }
+    let bytes = wtr
+        .into_inner()
+        .map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+pub fn keys_to_json(keys: &[AnswerKey]) -> Result<String, ExamWriterError> {
+    Ok(serde_json::to_string_pretty(keys)?)
+}
+
+pub fn write_keys_csv(filename: &str, keys: &[AnswerKey]) -> Result<(), ExamWriterError> {
+    fs::write(filename, keys_to_csv(keys)?)?;
+    Ok(())
+}
+
+pub fn write_keys_json(filename: &str, keys: &[AnswerKey]) -> Result<(), ExamWriterError> {
+    fs::write(filename, keys_to_json(keys)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{Choice, ChoiceOrdering, Choices, CorrectChoice, Question};
+
+    fn version() -> Exam {
+        let choices = |correct: u32, ordering: Vec<u32>| {
+            Some(Choices(
+                vec![Choice::new("a"), Choice::new("b"), Choice::new("c")],
+                CorrectChoice(correct),
+                Some(ChoiceOrdering(ordering)),
+            ))
+        };
+        Exam {
+            name: "v1".to_string(),
+            preamble: None,
+            questions: Some(vec![
+                Question::new("first", 1, choices(2, vec![1, 2, 0])),
+                Question::new("second", 2, choices(0, vec![0, 2, 1])),
+                Question::new("third", 3, None),
+            ]),
+            ordering: Some(vec![1, 2, 0]),
+        }
+    }
+
+    #[test]
+    fn key_follows_question_ordering() {
+        let keys = answer_keys(&[version()]);
+        assert_eq!(keys[0].version, "CODE01");
+        let answers: Vec<(u32, u32, &str)> = keys[0]
+            .answers
+            .iter()
+            .map(|e| (e.question, e.master_question, e.answer.as_str()))
+            .collect();
+        assert_eq!(answers, vec![(1, 2, "A"), (2, 3, ""), (3, 1, "C")]);
+    }
+
+    #[test]
+    fn key_without_options() {
+        let mut ex = version();
+        if let Some(qs) = ex.questions.as_mut() {
+            qs[2].choices = Some(Choices(vec![], CorrectChoice(0), None));
+        }
+        let key = AnswerKey::from_exam("CODE01", &ex);
+        assert_eq!(key.answers[1].answer, "");
+    }
+
+    #[test]
+    fn key_with_several_correct_choices() {
+        let mut ex = version();
+        if let Some(cs) = ex.questions.as_mut().and_then(|qs| qs[0].choices.as_mut()) {
+            cs.0[1].correct = true;
+        }
+        let key = AnswerKey::from_exam("CODE01", &ex);
+        let entry = &key.answers[2];
+        assert_eq!(entry.answer, "AC");
+        assert_eq!(entry.score("CA", Scoring::AllOrNothing), 1.0);
+        assert_eq!(entry.score("A", Scoring::AllOrNothing), 0.0);
+        assert_eq!(entry.score("a", Scoring::Partial), 0.5);
+        assert_eq!(key.score(&["A", "", "AC"], Scoring::AllOrNothing), 2.0);
+    }
+
+    #[test]
+    fn key_csv() {
+        let csv = keys_to_csv(&answer_keys(&[version()])).unwrap();
+        assert_eq!(
+            csv,
+            "version,question,answer,master_question\nCODE01,1,A,2\nCODE01,2,,3\nCODE01,3,C,1\n"
+        );
+    }
+
+    #[test]
+    fn key_json_round_trip() {
+        let keys = answer_keys(&[version()]);
+        let json = keys_to_json(&keys).unwrap();
+        let back: Vec<AnswerKey> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, keys);
+    }
+}
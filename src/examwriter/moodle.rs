@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::{
+    errors::ExamWriterError,
+    shuffler::{strip_variables, version_code, Choices, Exam, Question},
+};
+
+/// Renders Moodle XML with one `multichoice` question per question with
+/// choices and an `essay` question for the others.
+///
+/// The questions of `master` go to the category `<master name>/Group <n>`
+/// and those of every version to `CODE01/Group <n>`, ... Inline math
+/// `$...$` becomes `\(...\)` and display math `$$...$$` becomes `\[...\]`.
+///
+/// Correct choices get a fraction of 100 split between them; when a question
+/// has several, every wrong choice takes away the same share. Versions keep
+/// their printed choice order, while Moodle may shuffle the choices of the
+/// master unless one of them is pinned.
+pub fn to_moodle_xml(master: &Exam, versions: &[Exam]) -> String {
+    let mut doc = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<quiz>\n");
+    doc.push_str(&exam_questions(master, &master.name, true));
+    versions.iter().enumerate().for_each(|(i, version)| {
+        doc.push_str(&exam_questions(version, &version_code(i), false));
+    });
+    doc.push_str("</quiz>\n");
+    doc
+}
+
+pub fn write_moodle_xml(
+    filename: &str,
+    master: &Exam,
+    versions: &[Exam],
+) -> Result<(), ExamWriterError> {
+    if master.ordered_questions().is_empty() {
+        return Err(ExamWriterError::NoQuestions);
+    }
+    fs::write(filename, to_moodle_xml(master, versions))?;
+    Ok(())
+}
+
+fn exam_questions(ex: &Exam, category: &str, shuffle_answers: bool) -> String {
+    let mut groups: BTreeMap<u32, Vec<&Question>> = BTreeMap::new();
+    ex.ordered_questions()
+        .into_iter()
+        .for_each(|q| groups.entry(q.group).or_default().push(q));
+    let mut out = String::new();
+    for (group, qs) in groups {
+        out.push_str(&format!(
+            "  <question type=\"category\">\n    <category><text>$course$/{}/Group {}</text></category>\n  </question>\n",
+            escape(category),
+            group
+        ));
+        for q in qs {
+            out.push_str(&question_xml(q, &ex.name, shuffle_answers));
+        }
+    }
+    out
+}
+
+fn question_xml(q: &Question, exam_name: &str, shuffle_answers: bool) -> String {
+    let kind = if q.choices.is_some() {
+        "multichoice"
+    } else {
+        "essay"
+    };
+    let grade = q
+        .metadata
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("points"))
+        .and_then(|(_, v)| v.parse::<f64>().ok())
+        .unwrap_or(1.0);
+    let mut out = format!(
+        "  <question type=\"{}\">\n    <name><text>{} {}</text></name>\n    <questiontext format=\"html\">\n      {}\n    </questiontext>\n    <defaultgrade>{}</defaultgrade>\n",
+        kind,
+        escape(exam_name),
+        q.order,
+        html_text(&strip_variables(&q.text)),
+        grade
+    );
+    if let Some(cs) = &q.choices {
+        out.push_str(&choices_xml(cs, shuffle_answers));
+    }
+    out.push_str("  </question>\n");
+    out
+}
+
+fn choices_xml(cs: &Choices, shuffle_answers: bool) -> String {
+    let correct = cs.correct_positions();
+    let share = 100.0 / correct.len() as f64;
+    let wrong = if correct.len() > 1 { -share } else { 0.0 };
+    let shuffle = shuffle_answers && cs.0.iter().all(|c| !c.pinned);
+    let mut out = format!(
+        "    <single>{}</single>\n    <shuffleanswers>{}</shuffleanswers>\n    <answernumbering>ABCD</answernumbering>\n",
+        correct.len() == 1,
+        shuffle as u8
+    );
+    cs.ordered().into_iter().enumerate().for_each(|(i, c)| {
+        let fraction = if correct.contains(&(i as u32)) {
+            share
+        } else {
+            wrong
+        };
+        out.push_str(&format!(
+            "    <answer fraction=\"{}\" format=\"html\">\n      {}\n    </answer>\n",
+            fraction_text(fraction),
+            html_text(&c.text)
+        ));
+    });
+    out
+}
+
+/// Fractions as Moodle writes them, with at most five decimals.
+pub(super) fn fraction_text(fraction: f64) -> String {
+    let text = format!("{:.5}", fraction);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// A `<text>` element holding `tex` as HTML, with the math delimiters
+/// converted for MathJax.
+fn html_text(tex: &str) -> String {
+    let html = escape(&convert_math(tex));
+    format!(
+        "<text><![CDATA[{}]]></text>",
+        html.replace("]]>", "]]]]><![CDATA[>")
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Replaces `$$...$$` by `\[...\]` and `$...$` by `\(...\)`. Outside math,
+/// `\$` becomes a plain dollar; unbalanced dollars are left alone.
+pub(super) fn convert_math(tex: &str) -> String {
+    let mut out = String::new();
+    let mut rest = tex;
+    while let Some(start) = find_dollar(rest) {
+        let display = rest[start..].starts_with("$$");
+        let (open, close, delimiter) = if display {
+            ("\\[", "\\]", "$$")
+        } else {
+            ("\\(", "\\)", "$")
+        };
+        let body_start = start + delimiter.len();
+        let end = if display {
+            rest[body_start..].find("$$")
+        } else {
+            find_dollar(&rest[body_start..])
+        };
+        match end {
+            Some(end) => {
+                out.push_str(&rest[..start].replace("\\$", "$"));
+                out.push_str(open);
+                out.push_str(&rest[body_start..body_start + end]);
+                out.push_str(close);
+                rest = &rest[body_start + end + delimiter.len()..];
+            }
+            None => break,
+        }
+    }
+    out.push_str(&rest.replace("\\$", "$"));
+    out
+}
+
+/// Position of the first `$` not escaped as `\$`.
+fn find_dollar(text: &str) -> Option<usize> {
+    text.char_indices()
+        .find(|(i, c)| *c == '$' && !text[..*i].ends_with('\\'))
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{Choice, ChoiceOrdering, CorrectChoice};
+
+    fn master() -> Exam {
+        let mut second = Question::new(
+            "Which are even?",
+            2,
+            Some(Choices(
+                vec![
+                    Choice::new("2"),
+                    Choice::new("3"),
+                    Choice {
+                        correct: true,
+                        ..Choice::new("4")
+                    },
+                    Choice::pinned("None"),
+                ],
+                CorrectChoice(0),
+                None,
+            )),
+        );
+        second.group = 2;
+        second
+            .metadata
+            .insert("Points".to_string(), "2".to_string());
+        Exam {
+            name: "Major 1".to_string(),
+            preamble: None,
+            questions: Some(vec![
+                Question::new(
+                    "What is $1+1$ if $a < b$?",
+                    1,
+                    Some(Choices(
+                        vec![Choice::new("$2$"), Choice::new("$3$")],
+                        CorrectChoice(0),
+                        None,
+                    )),
+                ),
+                second,
+                Question::new("Prove $$x^2 \\ge 0$$ for \\$5.", 3, None),
+            ]),
+            ordering: None,
+        }
+    }
+
+    #[test]
+    fn converts_math_delimiters() {
+        assert_eq!(
+            convert_math("$a$ and $$b$$ cost \\$3, $c"),
+            "\\(a\\) and \\[b\\] cost $3, $c"
+        );
+    }
+
+    #[test]
+    fn moodle_questions_and_categories() {
+        let xml = to_moodle_xml(&master(), &[]);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<quiz>\n"));
+        assert!(xml.contains("<text>$course$/Major 1/Group 1</text>"));
+        assert!(xml.contains("<text>$course$/Major 1/Group 2</text>"));
+        assert!(xml.contains("<![CDATA[What is \\(1+1\\) if \\(a &lt; b\\)?]]>"));
+        assert!(xml.contains("<question type=\"essay\">"));
+        assert!(xml.find("Group 2").unwrap() > xml.find("Prove").unwrap());
+        assert!(xml.contains(
+            "<answer fraction=\"100\" format=\"html\">\n      <text><![CDATA[\\(2\\)]]></text>"
+        ));
+        assert!(xml.contains("<answer fraction=\"0\" format=\"html\">"));
+        assert!(xml.contains("<defaultgrade>2</defaultgrade>"));
+    }
+
+    #[test]
+    fn moodle_several_correct_choices() {
+        let xml = to_moodle_xml(&master(), &[]);
+        let second = &xml[xml.find("Which are even?").unwrap()..];
+        assert!(second.contains("<single>false</single>\n    <shuffleanswers>0</shuffleanswers>"));
+        assert_eq!(second.matches("fraction=\"50\"").count(), 2);
+        assert_eq!(second.matches("fraction=\"-50\"").count(), 2);
+    }
+
+    #[test]
+    fn moodle_versions_keep_printed_order() {
+        let mut version = master();
+        if let Some(qs) = version.questions.as_mut() {
+            qs[0].choices.as_mut().unwrap().2 = Some(ChoiceOrdering(vec![1, 0]));
+            qs[0].choices.as_mut().unwrap().1 = CorrectChoice(1);
+        }
+        version.ordering = Some(vec![2, 1, 0]);
+        let xml = to_moodle_xml(&master(), &[version]);
+        let code = &xml[xml.find("$course$/CODE01/Group 1").unwrap()..];
+        assert!(code.contains(
+            "<shuffleanswers>0</shuffleanswers>\n    <answernumbering>ABCD</answernumbering>\n    <answer fraction=\"0\" format=\"html\">\n      <text><![CDATA[\\(3\\)]]></text>"
+        ));
+    }
+}
@@ -0,0 +1,430 @@
+use std::fs;
+use std::io::{Cursor, Write};
+
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use super::{choice_letter, moodle::convert_math};
+use crate::{
+    errors::ExamWriterError,
+    shuffler::{strip_variables, Choices, Exam, Question},
+};
+
+/// The IMS QTI version of a package. QTI 3.0 uses the same structure as 2.1
+/// with `qti-` prefixed, kebab-case names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QtiVersion {
+    #[default]
+    V2_1,
+    V3_0,
+}
+
+impl QtiVersion {
+    fn namespace(self) -> &'static str {
+        match self {
+            QtiVersion::V2_1 => "http://www.imsglobal.org/xsd/imsqti_v2p1",
+            QtiVersion::V3_0 => "http://www.imsglobal.org/xsd/imsqtiasi_v3p0",
+        }
+    }
+
+    fn manifest_namespace(self) -> &'static str {
+        match self {
+            QtiVersion::V2_1 => "http://www.imsglobal.org/xsd/imscp_v1p1",
+            QtiVersion::V3_0 => "http://www.imsglobal.org/xsd/qti/qtiv3p0/imscp_v1p1",
+        }
+    }
+
+    fn match_correct(self) -> &'static str {
+        match self {
+            QtiVersion::V2_1 => {
+                "http://www.imsglobal.org/question/qti_v2p1/rptemplates/match_correct"
+            }
+            QtiVersion::V3_0 => {
+                "https://purl.imsglobal.org/spec/qti/v3p0/rptemplates/match_correct.xml"
+            }
+        }
+    }
+
+    /// The resource type of an item (`kind` is `item`) or a test in the
+    /// manifest.
+    fn resource_type(self, kind: &str) -> String {
+        match self {
+            QtiVersion::V2_1 => format!("imsqti_{}_xmlv2p1", kind),
+            QtiVersion::V3_0 => format!("imsqti_{}_xmlv3p0", kind),
+        }
+    }
+
+    fn schema(self) -> (&'static str, &'static str) {
+        match self {
+            QtiVersion::V2_1 => ("QTIv2.1 Package", "1.0.0"),
+            QtiVersion::V3_0 => ("QTI Package", "3.0.0"),
+        }
+    }
+
+    /// The name of a QTI element, given by its QTI 2.1 name.
+    fn element(self, name: &str) -> String {
+        match self {
+            QtiVersion::V2_1 => name.to_string(),
+            QtiVersion::V3_0 => format!("qti-{}", kebab_case(name)),
+        }
+    }
+
+    /// The name of a QTI attribute, given by its QTI 2.1 name.
+    fn attribute(self, name: &str) -> String {
+        match self {
+            QtiVersion::V2_1 => name.to_string(),
+            QtiVersion::V3_0 => kebab_case(name),
+        }
+    }
+}
+
+impl std::str::FromStr for QtiVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "2.1" | "2" => Ok(QtiVersion::V2_1),
+            "3.0" | "3" => Ok(QtiVersion::V3_0),
+            other => Err(format!(
+                "unknown QTI version `{}`, expected 2.1 or 3.0",
+                other
+            )),
+        }
+    }
+}
+
+pub(crate) fn kebab_case(name: &str) -> String {
+    name.chars().fold(String::new(), |mut out, c| {
+        if c.is_ascii_uppercase() {
+            out.push('-');
+        }
+        out.push(c.to_ascii_lowercase());
+        out
+    })
+}
+
+/// The files of a QTI package for `ex`, path and content: `imsmanifest.xml`,
+/// an `assessment.xml` test listing the questions in printed order and
+/// `items/item001.xml`, ... with one item per question.
+///
+/// Questions with choices become a `choiceInteraction` with `shuffle="false"`,
+/// so that the printed order of the choices is kept, and a correct response
+/// naming the correct choices by their letter. Pinned choices are marked
+/// `fixed="true"`; questions without choices
+/// become an `extendedTextInteraction`. Math is written for MathJax as in
+/// [`to_moodle_xml`](super::to_moodle_xml).
+pub fn qti_files(ex: &Exam, version: QtiVersion) -> Vec<(String, String)> {
+    let items: Vec<(String, String)> = ex
+        .ordered_questions()
+        .into_iter()
+        .enumerate()
+        .map(|(i, q)| {
+            let identifier = format!("item{:03}", i + 1);
+            let xml = item_xml(q, &identifier, &ex.name, version);
+            (identifier, xml)
+        })
+        .collect();
+    let mut files = vec![
+        (
+            "imsmanifest.xml".to_string(),
+            manifest_xml(ex, &items, version),
+        ),
+        ("assessment.xml".to_string(), test_xml(ex, &items, version)),
+    ];
+    files.extend(
+        items
+            .into_iter()
+            .map(|(identifier, xml)| (format!("items/{}.xml", identifier), xml)),
+    );
+    files
+}
+
+/// The zipped QTI package of [`qti_files`].
+pub fn to_qti_package(ex: &Exam, version: QtiVersion) -> Result<Vec<u8>, ExamWriterError> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, content) in qti_files(ex, version) {
+        zip.start_file(path, SimpleFileOptions::default())?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+pub fn write_qti(filename: &str, ex: &Exam, version: QtiVersion) -> Result<(), ExamWriterError> {
+    if ex.ordered_questions().is_empty() {
+        return Err(ExamWriterError::NoQuestions);
+    }
+    fs::write(filename, to_qti_package(ex, version)?)?;
+    Ok(())
+}
+
+fn manifest_xml(ex: &Exam, items: &[(String, String)], version: QtiVersion) -> String {
+    let (schema, schema_version) = version.schema();
+    let mut resources = format!(
+        "    <resource identifier=\"assessment\" type=\"{}\" href=\"assessment.xml\">\n      <file href=\"assessment.xml\"/>\n",
+        version.resource_type("test")
+    );
+    items.iter().for_each(|(identifier, _)| {
+        resources.push_str(&format!(
+            "      <dependency identifierref=\"{}\"/>\n",
+            identifier
+        ))
+    });
+    resources.push_str("    </resource>\n");
+    items.iter().for_each(|(identifier, _)| {
+        resources.push_str(&format!(
+            "    <resource identifier=\"{0}\" type=\"{1}\" href=\"items/{0}.xml\">\n      <file href=\"items/{0}.xml\"/>\n    </resource>\n",
+            identifier,
+            version.resource_type("item")
+        ))
+    });
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<manifest xmlns=\"{}\" identifier=\"{}\">\n  <metadata>\n    <schema>{}</schema>\n    <schemaversion>{}</schemaversion>\n  </metadata>\n  <organizations/>\n  <resources>\n{}  </resources>\n</manifest>\n",
+        version.manifest_namespace(),
+        identifier(&ex.name),
+        schema,
+        schema_version,
+        resources
+    )
+}
+
+fn test_xml(ex: &Exam, items: &[(String, String)], version: QtiVersion) -> String {
+    let e = |name: &str| version.element(name);
+    let a = |name: &str| version.attribute(name);
+    let refs: String = items
+        .iter()
+        .map(|(identifier, _)| {
+            format!(
+                "      <{0} identifier=\"{1}\" href=\"items/{1}.xml\"/>\n",
+                e("assessmentItemRef"),
+                identifier
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{test} xmlns=\"{ns}\" identifier=\"{id}\" title=\"{title}\">\n  <{part} identifier=\"part\" {nav}=\"linear\" {sub}=\"individual\">\n    <{section} identifier=\"section\" title=\"{title}\" visible=\"true\">\n{refs}    </{section}>\n  </{part}>\n</{test}>\n",
+        test = e("assessmentTest"),
+        ns = version.namespace(),
+        id = identifier(&ex.name),
+        title = escape(&ex.name),
+        part = e("testPart"),
+        nav = a("navigationMode"),
+        sub = a("submissionMode"),
+        section = e("assessmentSection"),
+        refs = refs
+    )
+}
+
+fn item_xml(q: &Question, identifier: &str, exam_name: &str, version: QtiVersion) -> String {
+    let e = |name: &str| version.element(name);
+    let a = |name: &str| version.attribute(name);
+    let (declaration, interaction, processing) = match &q.choices {
+        Some(cs) => choice_interaction(cs, version),
+        None => (
+            format!(
+                "  <{0} identifier=\"RESPONSE\" cardinality=\"single\" {1}=\"string\"/>\n",
+                e("responseDeclaration"),
+                a("baseType")
+            ),
+            format!(
+                "    <{} {}=\"RESPONSE\"/>\n",
+                e("extendedTextInteraction"),
+                a("responseIdentifier")
+            ),
+            String::new(),
+        ),
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{item} xmlns=\"{ns}\" identifier=\"{id}\" title=\"{title}\" adaptive=\"false\" {time}=\"false\">\n{declaration}  <{outcome} identifier=\"SCORE\" cardinality=\"single\" {base}=\"float\"/>\n  <{body}>\n{text}{interaction}  </{body}>\n{processing}</{item}>\n",
+        item = e("assessmentItem"),
+        ns = version.namespace(),
+        id = identifier,
+        title = escape(&format!("{} {}", exam_name, q.order)),
+        time = a("timeDependent"),
+        declaration = declaration,
+        outcome = e("outcomeDeclaration"),
+        base = a("baseType"),
+        body = e("itemBody"),
+        text = paragraphs(&strip_variables(&q.text)),
+        interaction = interaction,
+        processing = processing
+    )
+}
+
+/// The response declaration, interaction and response processing of a
+/// question with choices.
+fn choice_interaction(cs: &Choices, version: QtiVersion) -> (String, String, String) {
+    let e = |name: &str| version.element(name);
+    let a = |name: &str| version.attribute(name);
+    let correct = cs.correct_positions();
+    let cardinality = if correct.len() == 1 {
+        "single"
+    } else {
+        "multiple"
+    };
+    let values: String = correct
+        .iter()
+        .map(|i| format!("      <{0}>{1}</{0}>\n", e("value"), choice_letter(*i)))
+        .collect();
+    let declaration = format!(
+        "  <{decl} identifier=\"RESPONSE\" cardinality=\"{cardinality}\" {base}=\"identifier\">\n    <{correct}>\n{values}    </{correct}>\n  </{decl}>\n",
+        decl = e("responseDeclaration"),
+        cardinality = cardinality,
+        base = a("baseType"),
+        correct = e("correctResponse"),
+        values = values
+    );
+    let choices: String = cs
+        .ordered()
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            format!(
+                "      <{0} identifier=\"{1}\"{2}>{3}</{0}>\n",
+                e("simpleChoice"),
+                choice_letter(i as u32),
+                if c.pinned { " fixed=\"true\"" } else { "" },
+                html(&c.text).join("<br/>")
+            )
+        })
+        .collect();
+    let max_choices = if correct.len() == 1 { 1 } else { cs.0.len() };
+    let interaction = format!(
+        "    <{0} {1}=\"RESPONSE\" shuffle=\"false\" {2}=\"{3}\">\n{4}    </{0}>\n",
+        e("choiceInteraction"),
+        a("responseIdentifier"),
+        a("maxChoices"),
+        max_choices,
+        choices
+    );
+    let processing = format!(
+        "  <{} template=\"{}\"/>\n",
+        e("responseProcessing"),
+        version.match_correct()
+    );
+    (declaration, interaction, processing)
+}
+
+/// One `<p>` per paragraph of `tex`, paragraphs being separated by blank
+/// lines.
+fn paragraphs(tex: &str) -> String {
+    tex.split("\n\n")
+        .map(|p| html(p).join(" "))
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("    <p>{}</p>\n", p))
+        .collect()
+}
+
+/// The non-empty lines of `tex` as escaped HTML with MathJax delimiters.
+fn html(tex: &str) -> Vec<String> {
+    convert_math(tex)
+        .lines()
+        .map(|l| escape(l.trim()))
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `name` as an XML identifier: letters, digits, `-`, `_` and `.`, starting
+/// with a letter.
+fn identifier(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if id.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        id
+    } else {
+        format!("exam_{}", id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{Choice, ChoiceOrdering, CorrectChoice};
+
+    fn version() -> Exam {
+        Exam {
+            name: "CODE01".to_string(),
+            preamble: None,
+            questions: Some(vec![
+                Question::new(
+                    "Is $a < b$?",
+                    1,
+                    Some(Choices(
+                        vec![Choice::new("yes"), Choice::new("no")],
+                        CorrectChoice(1),
+                        Some(ChoiceOrdering(vec![1, 0])),
+                    )),
+                ),
+                Question::new("Explain.", 2, None),
+            ]),
+            ordering: Some(vec![1, 0]),
+        }
+    }
+
+    #[test]
+    fn qti_items_keep_printed_order() {
+        let files = qti_files(&version(), QtiVersion::V2_1);
+        let paths: Vec<&str> = files.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "imsmanifest.xml",
+                "assessment.xml",
+                "items/item001.xml",
+                "items/item002.xml"
+            ]
+        );
+        assert!(files[1]
+            .1
+            .contains("<assessmentItemRef identifier=\"item001\""));
+        assert!(files[2].1.contains("<extendedTextInteraction"));
+        let item = &files[3].1;
+        assert!(item.contains("<correctResponse>\n      <value>B</value>"));
+        assert!(item.contains("<choiceInteraction responseIdentifier=\"RESPONSE\" shuffle=\"false\" maxChoices=\"1\">"));
+        assert!(item.contains("<simpleChoice identifier=\"A\">no</simpleChoice>"));
+        assert!(item.contains("<p>Is \\(a &lt; b\\)?</p>"));
+        assert!(files[0]
+            .1
+            .contains("type=\"imsqti_item_xmlv2p1\" href=\"items/item002.xml\""));
+    }
+
+    #[test]
+    fn qti_3_names() {
+        let files = qti_files(&version(), QtiVersion::V3_0);
+        let item = &files[3].1;
+        assert!(item.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<qti-assessment-item xmlns=\"http://www.imsglobal.org/xsd/imsqtiasi_v3p0\""));
+        assert!(item.contains("<qti-choice-interaction response-identifier=\"RESPONSE\" shuffle=\"false\" max-choices=\"1\">"));
+        assert!(item.contains("<qti-response-declaration identifier=\"RESPONSE\" cardinality=\"single\" base-type=\"identifier\">"));
+        assert!(files[0].1.contains("imsqti_test_xmlv3p0"));
+    }
+
+    #[test]
+    fn qti_package_is_a_zip() {
+        let bytes = to_qti_package(&version(), QtiVersion::V2_1).unwrap();
+        let archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "assessment.xml",
+                "imsmanifest.xml",
+                "items/item001.xml",
+                "items/item002.xml"
+            ]
+        );
+    }
+}
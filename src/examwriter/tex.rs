@@ -0,0 +1,328 @@
+use std::fs;
+
+use crate::{
+    errors::ExamWriterError,
+    shuffler::{strip_variables, version_code, Choices, Exam, ExamSetting, Question},
+};
+
+const TEX_DOCUMENT_CLASS: &str =
+    "\\documentclass[amsfonts,bezier,leqno,fleqn,12pt,a4paper]{article}";
+const TEX_DEFAULT_LAYOUT: &str = r"\usepackage{graphicx}
+\topmargin=-.75in
+\textheight=26.5cm
+\footskip=.3in
+\oddsidemargin=-.1cm
+\textwidth=16.95cm
+\arraycolsep=.15in
+\labelsep=.75cm
+\renewcommand{\theequation}{\alph{equation}}
+\thicklines
+\pagestyle{myheadings}";
+const TEX_INSTRUCTIONS: &str = r"\underline{\large{\bf Important Instructions:}}
+\begin{enumerate}
+    \begin{normalsize}
+        \item  All types of calculators, pagers or mobile phones are NOT allowed during the examination.
+        \item  Use HB 2.5 pencils only.
+        \item  Use a good eraser. DO NOT use the erasers attached to the pencil.
+        \item  Write your name, ID number and Section number on the examination paper and in the upper left corner of the answer sheet.
+        \item  When bubbling your ID number and Section number, be sure that the bubbles match with the numbers that you write.
+        \item  The Test Code Number is already bubbled in your answer sheet. Make sure that it is the same as that printed on your question paper.
+        \item  When bubbling, make sure that the bubbled space is fully covered.
+        \item  When erasing a bubble, make sure that you do not leave any trace of penciling.
+    \end{normalsize}
+\end{enumerate}";
+
+/// Renders a complete LaTeX document: an exam cover, the master version with
+/// the correct answers marked and, for every shuffled version, a code cover
+/// page followed by its questions.
+///
+/// Question templates are printed in the master as written, `@{...}`
+/// placeholders included, without their `%{#vars}` block; the versions show
+/// the values drawn for them.
+pub fn to_tex(master: &Exam, setting: &ExamSetting, versions: &[Exam]) -> String {
+    let mut doc = String::new();
+    doc.push_str(TEX_DOCUMENT_CLASS);
+    doc.push('\n');
+    doc.push_str(TEX_DEFAULT_LAYOUT);
+    doc.push('\n');
+    if let Some(preamble) = &master.preamble {
+        doc.push_str(preamble);
+        doc.push('\n');
+    }
+    doc.push_str("\n\\begin{document}\n\n");
+    doc.push_str(&exam_cover(master, setting, versions.len()));
+    doc.push_str(&master_cover(setting));
+    doc.push_str(&page_header(setting, "MASTER"));
+    doc.push_str(&questions_body(master, true));
+    versions.iter().enumerate().for_each(|(i, version)| {
+        let code = version_code(i);
+        doc.push_str(&code_cover(version, setting, &code));
+        doc.push_str(&page_header(setting, &code));
+        doc.push_str(&questions_body(version, false));
+    });
+    doc.push_str("\\end{document}\n");
+    doc
+}
+
+pub fn write_tex(
+    filename: &str,
+    master: &Exam,
+    setting: &ExamSetting,
+    versions: &[Exam],
+) -> Result<(), ExamWriterError> {
+    if master.ordered_questions().is_empty() {
+        return Err(ExamWriterError::NoQuestions);
+    }
+    fs::write(filename, to_tex(master, setting, versions))?;
+    Ok(())
+}
+
+fn exam_cover(master: &Exam, setting: &ExamSetting, noversions: usize) -> String {
+    format!(
+        r"\thispagestyle{{empty}}
+\begin{{center}}
+    \begin{{large}}
+        {university} \\
+        {department} \\
+        \vspace*{{2cm}}
+        {{\bf {coursecode} }}  \\
+        {{\bf {examname} }}  \\
+        {{\bf {term} }}  \\
+        {{\bf {examdate} }}  \\
+        \vspace*{{3cm}}
+        {{\bf{{\Huge{{\fbox{{EXAM COVER}}}}}}}}\\
+        \vspace*{{2cm}}
+        {{\bf Number of versions: {noversions} }}  \\
+        {{\bf Number of questions: {noquestions} }}  \\
+        \vspace*{{0.2cm}}
+    \end{{large}}
+\end{{center}}
+
+\newpage
+
+",
+        university = setting.university,
+        department = setting.department,
+        coursecode = setting.coursecode,
+        examname = setting.examname,
+        term = setting.term,
+        examdate = setting.examdate,
+        noversions = noversions,
+        noquestions = master.ordered_questions().len(),
+    )
+}
+
+fn master_cover(setting: &ExamSetting) -> String {
+    format!(
+        r"\thispagestyle{{empty}}
+\begin{{center}}
+    \begin{{large}}
+        {university} \\
+        {department} \\
+        {{\bf {coursecode} }} \\
+        {{\bf {examname} }} \\
+        {{\bf {term} }}  \\
+        {{\bf {examdate} }}  \\
+        {{\bf Net Time Allowed: {timeallowed} }}  \\
+        \vspace*{{6cm}}
+        {{\bf {{\Huge{{MASTER VERSION}}}}}}  \\
+    \end{{large}}
+\end{{center}}
+
+\newpage
+
+",
+        university = setting.university,
+        department = setting.department,
+        coursecode = setting.coursecode,
+        examname = setting.examname,
+        term = setting.term,
+        examdate = setting.examdate,
+        timeallowed = setting.timeallowed,
+    )
+}
+
+fn code_cover(version: &Exam, setting: &ExamSetting, code: &str) -> String {
+    format!(
+        r"\newpage
+
+\thispagestyle{{empty}}
+\begin{{center}}
+    \begin{{large}}
+        {university} \\
+        {department} \\
+        \vspace*{{4.5cm}}
+        {{\bf \fbox{{ {code} }} }}  \hfill {{\bf \fbox{{ {code} }}}} \\
+        {{\bf {coursecode} }}  \\
+        {{\bf {examname} }}  \\
+        {{\bf {term} }}  \\
+        {{\bf {examdate} }}  \\
+        {{\bf Net Time Allowed: {timeallowed} }}  \\
+        \vspace*{{0.2cm}}
+    \end{{large}}
+\end{{center}}
+
+\large{{Name:  }}\hrulefill
+
+\vspace{{3mm}}
+
+\large{{ID: }} \hrulefill \large{{  Sec: }} \hrulefill
+
+\vspace{{1cm}}
+
+\large{{\bf{{Check that this exam has {{\underline{{ {noquestions} }}}} questions.}}}}
+
+\vspace{{1cm}}
+
+{instructions}
+
+\newpage
+
+",
+        university = setting.university,
+        department = setting.department,
+        code = code,
+        coursecode = setting.coursecode,
+        examname = setting.examname,
+        term = setting.term,
+        examdate = setting.examdate,
+        timeallowed = setting.timeallowed,
+        noquestions = version.ordered_questions().len(),
+        instructions = TEX_INSTRUCTIONS,
+    )
+}
+
+fn page_header(setting: &ExamSetting, label: &str) -> String {
+    let title: Vec<&str> = [
+        setting.term.as_str(),
+        setting.coursecode.as_str(),
+        setting.examname.as_str(),
+    ]
+    .into_iter()
+    .filter(|s| !s.is_empty())
+    .collect();
+    format!(
+        "\\renewcommand{{\\thepage}}{{\\noindent {} \\hfill Page {{\\bf \\arabic{{page}} }} \\hfill {{\\bf \\fbox{{ {} }}}}}}\n\\setcounter{{page}}{{1}}\n\n",
+        title.join(", "),
+        label
+    )
+}
+
+fn questions_body(exam: &Exam, mark_correct: bool) -> String {
+    let items: Vec<String> = exam
+        .ordered_questions()
+        .into_iter()
+        .map(|q| question_item(q, mark_correct))
+        .collect();
+    format!(
+        "\\begin{{large}}\n\\begin{{enumerate}}\n\n{}\\end{{enumerate}}\n\\end{{large}}\n\n\\newpage\n\n",
+        items.join("\\vspace {3.5cm}\n\n")
+    )
+}
+
+fn question_item(q: &Question, mark_correct: bool) -> String {
+    let options = match &q.choices {
+        Some(cs) => choices_list(cs, mark_correct),
+        None => String::new(),
+    };
+    format!(
+        "\\item {}\n\\vspace {{0.3in}}\n\\setcounter{{equation}}{{0}}\n\n{}\n",
+        strip_variables(&q.text),
+        options
+    )
+}
+
+fn choices_list(cs: &Choices, mark_correct: bool) -> String {
+    let correct = cs.correct_positions();
+    let items: Vec<String> = cs
+        .ordered()
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            if mark_correct && correct.contains(&(i as u32)) {
+                format!("\\item {}\\hrulefill {{\\small (correct)}}\n", c.text)
+            } else {
+                format!("\\item {}\n", c.text)
+            }
+        })
+        .collect();
+    format!(
+        "\\begin{{enumerate}}\n{}\n\\end{{enumerate}}\n",
+        items.concat()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{shuffle_exam_seeded, Choice, ChoiceOrdering, CorrectChoice};
+
+    fn master() -> Exam {
+        Exam {
+            name: "master".to_string(),
+            preamble: Some("\\usepackage{amsfonts}".to_string()),
+            questions: Some(vec![
+                Question::new(
+                    "What is $1+1$?",
+                    1,
+                    Some(Choices(
+                        vec![Choice::new("2"), Choice::new("3"), Choice::new("4")],
+                        CorrectChoice(0),
+                        None,
+                    )),
+                ),
+                Question::new("Prove it.", 2, None),
+            ]),
+            ordering: None,
+        }
+    }
+
+    fn setting() -> ExamSetting {
+        ExamSetting {
+            term: "Term 213".to_string(),
+            coursecode: "MATH102".to_string(),
+            examname: "Major Exam 1".to_string(),
+            ..ExamSetting::new()
+        }
+    }
+
+    #[test]
+    fn tex_has_preamble_covers_and_headers() {
+        let ex = master();
+        let versions = vec![
+            shuffle_exam_seeded(&ex, Some("v1"), 1),
+            shuffle_exam_seeded(&ex, Some("v2"), 1),
+        ];
+        let tex = to_tex(&ex, &setting(), &versions);
+        assert!(tex.contains("\\usepackage{amsfonts}\n\n\\begin{document}"));
+        assert!(tex.contains("MASTER VERSION"));
+        assert!(tex.contains("\\fbox{ CODE01 }"));
+        assert!(tex.contains("\\fbox{ CODE02 }"));
+        assert!(!tex.contains("CODE03"));
+        assert!(tex.contains("\\noindent Term 213, MATH102, Major Exam 1 \\hfill"));
+        assert!(tex.trim_end().ends_with("\\end{document}"));
+    }
+
+    #[test]
+    fn tex_marks_correct_choice_in_master_only() {
+        let ex = master();
+        let version = Exam {
+            name: "v1".to_string(),
+            preamble: None,
+            questions: Some(vec![Question::new(
+                "What is $1+1$?",
+                1,
+                Some(Choices(
+                    vec![Choice::new("2"), Choice::new("3"), Choice::new("4")],
+                    CorrectChoice(2),
+                    Some(ChoiceOrdering(vec![2, 1, 0])),
+                )),
+            )]),
+            ordering: Some(vec![0]),
+        };
+        let tex = to_tex(&ex, &setting(), &[version]);
+        assert_eq!(tex.matches("(correct)").count(), 1);
+        assert!(tex.contains("\\item 2\\hrulefill {\\small (correct)}"));
+        assert!(tex.contains("\\item 4\n\\item 3\n\\item 2\n"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Question groups named by the input formats: the `group` attribute of a
+//! TeX question tag and the categories of Moodle XML and GIFT files.
+use std::collections::BTreeMap;
+
+use crate::constants::QUESTION_GROUP_ATTR;
+
+/// Group of a question from whatever sits between `%{#q` and `}`, e.g.
+/// `%{#q group=2}`; group 1 when none is given. Bad attributes are reported
+/// by [`crate::diagnostics::check_tex`].
+pub(crate) fn question_group(attrs: &str) -> Result<u32, String> {
+    attrs
+        .split_whitespace()
+        .try_fold(1, |_, attr| match attr.split_once('=') {
+            Some((QUESTION_GROUP_ATTR, value)) => value
+                .parse()
+                .map_err(|_| format!("the group `{}` is not a number", value)),
+            _ => Err(format!(
+                "`{}` is not a question attribute, expected `{}=<number>`",
+                attr, QUESTION_GROUP_ATTR
+            )),
+        })
+}
+
+/// The group of the questions under `category`. A category ending in
+/// `Group <n>`, as written by the Moodle and GIFT writers, gives group `n`;
+/// any other category the next free number.
+pub(crate) fn category_group(category: &str, groups: &mut BTreeMap<String, u32>) -> u32 {
+    if let Some(group) = groups.get(category) {
+        return *group;
+    }
+    let last = category.rsplit('/').next().unwrap_or_default().trim();
+    let numbered = last
+        .strip_prefix("Group ")
+        .and_then(|n| n.trim().parse::<u32>().ok());
+    let group = numbered.unwrap_or_else(|| {
+        (1..)
+            .find(|g| !groups.values().any(|v| v == g))
+            .unwrap_or(1)
+    });
+    groups.insert(category.to_string(), group);
+    group
+}
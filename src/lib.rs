@@ -1,5 +1,9 @@
 pub mod examreader;
+pub mod examwriter;
 pub use examreader::*;
+pub mod bank;
 pub mod constants;
+pub mod diagnostics;
 pub mod errors;
+mod groups;
 pub mod shuffler;
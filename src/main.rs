@@ -1,67 +1,561 @@
-// #![allow(dead_code)]
-
-// use mc_exam_randomizer::{
-//     errors::ExamReaderError,
-//     shuffler::{shuffle_exam, Exam},
-// };
-
-// fn main() {
-//     // test_shuffle_option();
-//     //test_shuffle_exam();
-//     //
-//     // let filename_tex = "files/testsing/exam.tex";
-//     // match Exam::from_tex(filename_tex, "master") {
-//     //     Ok(ex) => {
-//     //         let ex_json = serde_json::to_string(&ex).unwrap();
-//     //         println!("master {:#?}", ex_json);
-//     //         let version_1 = shuffle_exam(&ex, Some("version 1"));
-//     //         let v1_json = serde_json::to_string(&version_1).unwrap();
-//     //         println!("ver1 {:#?}", v1_json);
-//     //         println!("ver1 {:#?}", version_1);
-//     //     }
-//     //     Err(err) => match err {
-//     //         ExamReaderError::TemplateError(err_st) => {
-//     //             println!("ERR: {:#?}", err_st);
-//     //         }
-//     //         _ => println!("Err: {:#?}", err),
-//     //     },
-//     // };
-//     // let filename_csv = "files/sample.csv";
-//     // match Exam::from_csv(filename_csv, "master") {
-//     //     Ok(ex) => {
-//     //         // let ex_json = serde_json::to_string(&ex).unwrap();
-//     //         // println!("master {:#?}", ex_json);
-//     //         let version_1 = shuffle_exam(&ex, Some("version 1"));
-//     //         // let v1_json = serde_json::to_string(&version_1).unwrap();
-//     //         // println!("ver1 {:#?}", v1_json);
-//     //         // println!("ver1 {:#?}", version_1);
-//     //     }
-//     //     Err(err) => match err {
-//     //         ExamReaderError::TemplateError(err_st) => {
-//     //             println!("ERR: {:#?}", err_st);
-//     //         }
-//     //         _ => println!("Err: {:#?}", err),
-//     //     },
-//     // };
-//     // let filename_csv = "files/sample.txt";
-//     // match Exam::from_txt(filename_csv, "master") {
-//     //     Ok(ex) => {
-//     //         // let ex_json = serde_json::to_string(&ex).unwrap();
-//     //         // println!("master {:#?}", ex_json);
-//     //         let version_1 = shuffle_exam(&ex, Some("version 1"));
-//     //         // let v1_json = serde_json::to_string(&version_1).unwrap();
-//     //         // println!("ver1 {:#?}", v1_json);
-//     //         // println!("ver1 {:#?}", version_1);
-//     //     }
-//     //     Err(err) => match err {
-//     //         ExamReaderError::TemplateError(err_st) => {
-//     //             println!("ERR: {:#?}", err_st);
-//     //         }
-//     //         _ => println!("Err: {:#?}", err),
-//     //     },
-//     // };
-//     // let qs_csv = from_csv(filename_csv);
-//     // println!("question from csv {:#?}", qs_csv)
-// }
-
-fn main() {}
+use std::{error::Error, path::Path, process};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use mc_exam_randomizer::{
+    bank, diagnostics,
+    errors::ExamReaderError,
+    examreader::{self, AnswerFormat, ColumnMapping, ReadMode, ReadOptions},
+    examwriter::{self, QtiVersion},
+    shuffler::{
+        shuffle_versions_seeded, AnswerBalance, Dissimilarity, Exam, ExamSetting, GroupShuffle,
+        Lint, QuestionPool, ShuffleOptions,
+    },
+};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Produce randomized versions of a multiple choice exam"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Fail on the rows of a CSV, TXT or XLSX file that cannot be read instead of
+    /// skipping them with a warning
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Header name of a CSV, TXT or XLSX column, e.g. `text=Stem` or `answer=Key|Correct`;
+    /// the fields are group, text, options (a prefix) and answer
+    #[arg(long = "column", global = true)]
+    columns: Vec<String>,
+    /// How the answer column names the correct options; with auto, an answer
+    /// that is one option by position and another by text is reported
+    #[arg(long, global = true, value_enum, default_value_t = AnswerMode::Auto)]
+    answer_format: AnswerMode,
+    /// Field separator of .csv files; .txt files are always tab separated
+    #[arg(long, global = true, value_enum, default_value_t = Delimiter::Comma)]
+    csv_delimiter: Delimiter,
+}
+
+impl Cli {
+    fn read_options(&self) -> Result<ReadOptions, String> {
+        let mut columns = ColumnMapping {
+            answer_format: self.answer_format.into(),
+            ..ColumnMapping::default()
+        };
+        for assignment in &self.columns {
+            columns.set(assignment)?;
+        }
+        let mode = if self.strict {
+            ReadMode::Strict
+        } else {
+            ReadMode::Lenient
+        };
+        Ok(ReadOptions {
+            mode,
+            columns,
+            csv_delimiter: self.csv_delimiter.into(),
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read an exam and write a printable LaTeX file with N shuffled versions
+    Generate {
+        /// Input exam (.tex, .json, .yaml, .csv, .txt, .xlsx, Moodle .xml, .gift, .aiken or a QTI package)
+        input: String,
+        /// Output .tex file
+        #[arg(short, long)]
+        output: String,
+        #[command(flatten)]
+        versions: VersionArgs,
+        /// Also write the answer keys (.csv or .json)
+        #[arg(short, long)]
+        key: Option<String>,
+        /// Also write the master and the versions as Moodle XML
+        #[arg(long)]
+        moodle: Option<String>,
+        /// Also write the master and the versions as GIFT
+        #[arg(long)]
+        gift: Option<String>,
+        /// Also write one QTI package per exam (master.zip, CODE01.zip, ...) to this directory
+        #[arg(long)]
+        qti: Option<String>,
+        /// QTI version of the packages, 2.1 or 3.0
+        #[arg(long, default_value = "2.1")]
+        qti_version: QtiVersion,
+    },
+    /// Parse an exam and report problems
+    Validate {
+        /// Input exam (.tex, .json, .yaml, .csv, .txt, .xlsx, Moodle .xml, .gift, .aiken or a QTI package)
+        input: String,
+    },
+    /// Report problems that would show up in the printed versions, such as
+    /// missing or repeated choices and repeated questions
+    Lint {
+        /// Input exam (.tex, .json, .yaml, .csv, .txt, .xlsx, Moodle .xml, .gift, .aiken or a QTI package)
+        input: String,
+    },
+    /// Convert an exam between formats (.json or .yaml bank, .csv, .txt, .tex, Moodle .xml,
+    /// .gift, .aiken or a QTI .zip package)
+    Convert {
+        /// Input exam (.tex, .json, .yaml, .csv, .txt, .xlsx, Moodle .xml, .gift, .aiken or a QTI package)
+        input: String,
+        /// Output file; the format follows the extension
+        output: String,
+        /// QTI version of a .zip output, 2.1 or 3.0
+        #[arg(long, default_value = "2.1")]
+        qti_version: QtiVersion,
+    },
+    /// Print the answer keys of the shuffled versions
+    Key {
+        /// Input exam (.tex, .json, .yaml, .csv, .txt, .xlsx, Moodle .xml, .gift, .aiken or a QTI package)
+        input: String,
+        #[command(flatten)]
+        versions: VersionArgs,
+        #[arg(short, long, value_enum, default_value_t = KeyFormat::Csv)]
+        format: KeyFormat,
+    },
+    /// Print the JSON Schema of the .json and .yaml bank format
+    Schema,
+}
+
+#[derive(clap::Args)]
+struct VersionArgs {
+    /// Number of versions, defaults to `numberofvestions` in the exam setting
+    #[arg(short = 'n', long)]
+    versions: Option<u32>,
+    /// Master seed; the same seed reproduces the same versions
+    #[arg(short, long)]
+    seed: Option<u64>,
+    /// How questions are shuffled with respect to their groups
+    #[arg(short, long, value_enum, default_value_t = GroupMode::Global)]
+    groups: GroupMode,
+    /// Spread the correct letters evenly over each version
+    #[arg(short, long)]
+    balanced: bool,
+    /// With --balanced, the most consecutive questions sharing a correct letter
+    #[arg(long, default_value_t = 2)]
+    max_run: usize,
+    /// With --balanced, how often a letter may be correct beyond its fair share
+    #[arg(long, default_value_t = 1)]
+    tolerance: u32,
+    /// Questions drawn from each group, e.g. `1:3,2:5`; defaults to `groups` in the exam setting
+    #[arg(long)]
+    pool: Option<String>,
+    /// Print this many choices per question, sampling the distractors
+    #[arg(long)]
+    choices: Option<usize>,
+    /// Let every version draw its own questions from the pools
+    #[arg(long)]
+    independent_draws: bool,
+    /// Least fraction of questions printed at different positions in any two versions
+    #[arg(long)]
+    min_position_change: Option<f64>,
+    /// Least fraction of differing correct answers between any two versions
+    #[arg(long)]
+    min_answer_change: Option<f64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GroupMode {
+    Global,
+    Within,
+    ShuffleGroups,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AnswerMode {
+    Auto,
+    Position,
+    Text,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Delimiter {
+    Comma,
+    Semicolon,
+    Tab,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum KeyFormat {
+    Csv,
+    Json,
+}
+
+impl From<GroupMode> for GroupShuffle {
+    fn from(mode: GroupMode) -> Self {
+        match mode {
+            GroupMode::Global => GroupShuffle::Global,
+            GroupMode::Within => GroupShuffle::WithinGroups,
+            GroupMode::ShuffleGroups => GroupShuffle::ShuffleGroups,
+        }
+    }
+}
+
+impl From<AnswerMode> for AnswerFormat {
+    fn from(mode: AnswerMode) -> Self {
+        match mode {
+            AnswerMode::Auto => AnswerFormat::Auto,
+            AnswerMode::Position => AnswerFormat::Position,
+            AnswerMode::Text => AnswerFormat::Text,
+        }
+    }
+}
+
+impl From<Delimiter> for u8 {
+    fn from(delimiter: Delimiter) -> Self {
+        match delimiter {
+            Delimiter::Comma => b',',
+            Delimiter::Semicolon => b';',
+            Delimiter::Tab => b'\t',
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let result = cli
+        .read_options()
+        .map_err(|err| err.into())
+        .and_then(|options| run(cli.command, &options));
+    if let Err(err) = result {
+        match err.downcast_ref::<ExamReaderError>() {
+            Some(ExamReaderError::Diagnostics(_)) => eprintln!("{}", err),
+            _ => eprintln!("error: {}", err),
+        }
+        process::exit(1);
+    }
+}
+
+fn run(command: Command, options: &ReadOptions) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Generate {
+            input,
+            output,
+            versions,
+            key,
+            moodle,
+            gift,
+            qti,
+            qti_version,
+        } => {
+            let (master, setting) = read_exam(&input, options)?;
+            let shuffled = shuffle(&master, &setting, &versions)?;
+            examwriter::write_tex(&output, &master, &setting, &shuffled)?;
+            if let Some(key_file) = key {
+                let keys = examwriter::answer_keys(&shuffled);
+                match extension(&key_file).as_str() {
+                    "json" => examwriter::write_keys_json(&key_file, &keys)?,
+                    _ => examwriter::write_keys_csv(&key_file, &keys)?,
+                }
+            }
+            if let Some(moodle_file) = moodle {
+                examwriter::write_moodle_xml(&moodle_file, &master, &shuffled)?;
+            }
+            if let Some(gift_file) = gift {
+                examwriter::write_gift(&gift_file, &master, &shuffled)?;
+            }
+            if let Some(dir) = qti {
+                std::fs::create_dir_all(&dir)?;
+                for ex in std::iter::once(&master).chain(&shuffled) {
+                    let file = Path::new(&dir).join(format!("{}.zip", ex.name));
+                    examwriter::write_qti(&file.to_string_lossy(), ex, qti_version)?;
+                }
+            }
+            println!("wrote {} versions to {}", shuffled.len(), output);
+        }
+        Command::Validate { input } => {
+            if extension(&input) == "tex" {
+                let found = examreader::check_tex(&input)?;
+                if !found.is_empty() {
+                    eprintln!("{}\n", diagnostics::render(&found));
+                }
+                let errors = found.iter().filter(|d| d.is_error()).count();
+                if errors > 0 {
+                    return Err(format!("{}: {} error(s)", input, errors).into());
+                }
+            }
+            let (master, setting) = read_exam(&input, options)?;
+            println!("{}: {} questions", input, master.ordered_questions().len());
+            for lint in lints(&input, &master, &setting) {
+                println!("warning: {}", lint);
+            }
+        }
+        Command::Lint { input } => {
+            let (master, setting) = read_exam(&input, options)?;
+            let lints = lints(&input, &master, &setting);
+            for lint in &lints {
+                println!("warning: {}", lint);
+            }
+            if !lints.is_empty() {
+                return Err(format!("{}: {} warning(s)", input, lints.len()).into());
+            }
+        }
+        Command::Convert {
+            input,
+            output,
+            qti_version,
+        } => {
+            let (master, setting) = read_exam(&input, options)?;
+            match extension(&output).as_str() {
+                "csv" => examwriter::write_csv(&output, &master)?,
+                "txt" => examwriter::write_txt(&output, &master)?,
+                "json" => master.write_json(&output, bank_setting(&setting))?,
+                "yaml" | "yml" => master.write_yaml(&output, bank_setting(&setting))?,
+                "tex" => examwriter::write_tex(&output, &master, &setting, &[])?,
+                "xml" => examwriter::write_moodle_xml(&output, &master, &[])?,
+                "gift" => examwriter::write_gift(&output, &master, &[])?,
+                "aiken" => examwriter::write_aiken(&output, &master)?,
+                "zip" => examwriter::write_qti(&output, &master, qti_version)?,
+                ext => return Err(format!("unsupported output format `{}`", ext).into()),
+            }
+        }
+        Command::Key {
+            input,
+            versions,
+            format,
+        } => {
+            let (master, setting) = read_exam(&input, options)?;
+            let keys = examwriter::answer_keys(&shuffle(&master, &setting, &versions)?);
+            match format {
+                KeyFormat::Csv => print!("{}", examwriter::keys_to_csv(&keys)?),
+                KeyFormat::Json => println!("{}", examwriter::keys_to_json(&keys)?),
+            }
+        }
+        Command::Schema => print!("{}", bank::BANK_SCHEMA),
+    }
+    Ok(())
+}
+
+fn extension(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Reads the master exam; unless in strict mode, the rows of a CSV, TXT or
+/// XLSX file that cannot be read are skipped with a warning. Moodle XML, GIFT and
+/// QTI questions of an unsupported type are always skipped with a warning.
+fn read_exam(
+    filename: &str,
+    options: &ReadOptions,
+) -> Result<(Exam, ExamSetting), ExamReaderError> {
+    let (questions, warnings) = match extension(filename).as_str() {
+        "tex" => {
+            let (exam, setting) = Exam::from_tex(filename, "master")?;
+            return Ok((exam, setting.unwrap_or_default()));
+        }
+        "json" => {
+            let (exam, setting) = Exam::from_json(filename)?;
+            return Ok((exam, setting.unwrap_or_default()));
+        }
+        "yaml" | "yml" => {
+            let (exam, setting) = Exam::from_yaml(filename)?;
+            return Ok((exam, setting.unwrap_or_default()));
+        }
+        "csv" => examreader::from_csv_with_options(filename, options)?,
+        "txt" => examreader::from_txt_with_options(filename, options)?,
+        "xlsx" => examreader::from_xlsx_with_options(filename, None, options)?,
+        "xml" => examreader::from_moodle_xml(filename)?,
+        "gift" => examreader::from_gift(filename)?,
+        "aiken" => (examreader::from_aiken(filename)?, vec![]),
+        "zip" => examreader::from_qti(filename)?,
+        _ if Path::new(filename).is_dir() => examreader::from_qti(filename)?,
+        ext => {
+            return Err(ExamReaderError::TemplateError(format!(
+                "unsupported input format `{}`",
+                ext
+            )))
+        }
+    };
+    if !warnings.is_empty() {
+        eprintln!("{}\n", diagnostics::render(&warnings));
+    }
+    let exam = Exam {
+        questions: Some(questions),
+        ..Exam::new("master")
+    };
+    Ok((exam, ExamSetting::new()))
+}
+
+/// Lints of the master, and of its setting for TeX templates and banks, the
+/// only inputs carrying a setting worth checking.
+fn lints(filename: &str, master: &Exam, setting: &ExamSetting) -> Vec<Lint> {
+    let mut lints = master.validate();
+    if matches!(
+        extension(filename).as_str(),
+        "tex" | "json" | "yaml" | "yml"
+    ) {
+        lints.extend(master.validate_setting(setting));
+    }
+    lints
+}
+
+/// The setting written to a bank, left out when nothing is set.
+fn bank_setting(setting: &ExamSetting) -> Option<&ExamSetting> {
+    (*setting != ExamSetting::new()).then_some(setting)
+}
+
+fn shuffle(
+    master: &Exam,
+    setting: &ExamSetting,
+    args: &VersionArgs,
+) -> Result<Vec<Exam>, Box<dyn Error>> {
+    let count = args.versions.unwrap_or(setting.numberofvestions);
+    if count == 0 {
+        return Err("the number of versions is neither given nor set in the exam setting".into());
+    }
+    let seed = match args.seed {
+        Some(seed) => seed,
+        None => {
+            let seed = rand::random();
+            eprintln!("using seed {}", seed);
+            seed
+        }
+    };
+    let same_for_all_versions = !args.independent_draws;
+    let pool = match &args.pool {
+        Some(groups) => QuestionPool::parse(groups, same_for_all_versions)?,
+        None => QuestionPool::parse(&setting.groups, same_for_all_versions).unwrap_or_else(|err| {
+            eprintln!("warning: the groups setting is ignored: {}", err);
+            None
+        }),
+    };
+    let options = ShuffleOptions {
+        pool,
+        choices_per_question: args.choices,
+        groups: args.groups.into(),
+        balance: if args.balanced {
+            Some(AnswerBalance {
+                max_run: args.max_run,
+                tolerance: args.tolerance,
+                ..AnswerBalance::default()
+            })
+        } else {
+            None
+        },
+        dissimilarity: if args.min_position_change.is_some() || args.min_answer_change.is_some() {
+            Some(Dissimilarity {
+                min_position_change: args.min_position_change.unwrap_or(0.0),
+                min_answer_change: args.min_answer_change.unwrap_or(0.0),
+                ..Dissimilarity::default()
+            })
+        } else {
+            None
+        },
+    };
+    Ok(shuffle_versions_seeded(
+        master,
+        count as usize,
+        seed,
+        &options,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("mc-exam-randomizer").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn parses_generate_arguments() {
+        let cli = parse(&[
+            "generate",
+            "exam.csv",
+            "-o",
+            "exam.tex",
+            "-n",
+            "3",
+            "--seed",
+            "7",
+            "--strict",
+            "--column",
+            "text=Stem",
+            "--answer-format",
+            "text",
+            "--csv-delimiter",
+            "tab",
+        ])
+        .unwrap();
+        let options = cli.read_options().unwrap();
+        assert_eq!(options.mode, ReadMode::Strict);
+        assert_eq!(options.columns.text, vec!["Stem"]);
+        assert_eq!(options.columns.answer_format, AnswerFormat::Text);
+        assert_eq!(options.csv_delimiter, b'\t');
+        match cli.command {
+            Command::Generate {
+                input,
+                output,
+                versions,
+                key,
+                ..
+            } => {
+                assert_eq!((input.as_str(), output.as_str()), ("exam.csv", "exam.tex"));
+                assert_eq!((versions.versions, versions.seed), (Some(3), Some(7)));
+                assert!(key.is_none());
+            }
+            _ => panic!("expected the generate command"),
+        }
+    }
+
+    #[test]
+    fn reads_leniently_by_default() {
+        let cli = parse(&["validate", "exam.csv"]).unwrap();
+        assert_eq!(
+            cli.read_options().unwrap(),
+            ReadOptions {
+                mode: ReadMode::Lenient,
+                columns: ColumnMapping::default(),
+                csv_delimiter: b',',
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse(&["generate", "exam.csv"]).is_err());
+        assert!(parse(&["key", "exam.csv", "--groups", "sideways"]).is_err());
+        assert!(parse(&["key", "exam.csv", "--answer-format", "letter"]).is_err());
+        assert!(parse(&["key", "exam.csv", "-n", "two"]).is_err());
+        let cli = parse(&["key", "exam.csv", "--column", "stem=Question"]).unwrap();
+        assert_eq!(
+            cli.read_options().unwrap_err(),
+            "unknown column field `stem`"
+        );
+    }
+
+    #[test]
+    fn reads_by_extension() {
+        let options = ReadOptions::default();
+        for file in [
+            "files/testing/template.tex",
+            "files/testing/bank.yaml",
+            "files/testing/sample.csv",
+            "files/testing/sample.txt",
+            "files/testing/sample.gift",
+            "files/testing/sample.aiken",
+            "files/testing/moodle.xml",
+            "files/testing/qti",
+        ] {
+            let (exam, _) = read_exam(file, &options).unwrap();
+            assert!(
+                !exam.questions.unwrap().is_empty(),
+                "{} has no questions",
+                file
+            );
+        }
+        let (_, setting) = read_exam("files/testing/bank.yaml", &options).unwrap();
+        assert_eq!(setting.numberofvestions, 4);
+        assert_eq!(
+            read_exam("exam.pdf", &options).unwrap_err().to_string(),
+            "Your input file is badly formatted: `unsupported input format `pdf``"
+        );
+    }
+}
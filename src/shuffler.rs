@@ -1,72 +1,681 @@
+pub mod balance;
+pub mod dissimilarity;
 pub mod exam;
+pub mod expression;
+pub mod group;
+pub mod lint;
+pub mod options;
+pub mod parametric;
+pub mod pool;
 pub mod question;
 pub mod setting;
 
+pub use balance::*;
+pub use dissimilarity::*;
 pub use exam::*;
+pub use expression::*;
+pub use group::*;
+pub use lint::*;
+pub use options::*;
+pub use parametric::*;
+pub use pool::*;
 pub use question::*;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::seq::{index::sample, SliceRandom};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 pub use setting::*;
 
-pub fn shuffle_questions(qs: &Vec<Question>) -> Vec<&Question> {
+use crate::errors::ShuffleError;
+
+pub fn shuffle_questions(qs: &[Question]) -> Vec<&Question> {
+    shuffle_questions_with_rng(qs, &mut thread_rng())
+}
+
+pub fn shuffle_questions_with_rng<'a, R: Rng + ?Sized>(
+    qs: &'a [Question],
+    rng: &mut R,
+) -> Vec<&'a Question> {
     let noq = qs.len() as u32;
     let mut vec: Vec<u32> = (0..noq).collect();
-    vec.shuffle(&mut thread_rng());
+    vec.shuffle(rng);
     let qs2: Vec<&Question> = vec
         .iter()
-        .enumerate()
-        .map(|(_i, ord)| qs.get(*ord as usize).unwrap())
+        .map(|ord| qs.get(*ord as usize).unwrap())
         .collect();
     qs2
 }
 
 pub fn shuffle_exam(ex: &Exam, name: Option<&str>) -> Exam {
-    let name = if let Some(nm) = name { nm } else { &ex.name };
+    shuffle_exam_with_rng(ex, name, &mut thread_rng())
+}
 
-    if let Some(qs) = &ex.questions {
-        let qs_shuffled: Vec<Question> = qs.into_iter().map(|q| shuffle_choices(q)).collect();
-        let noq = qs.len() as u32;
-        let mut ordering: Vec<u32> = (0..noq).collect();
-        ordering.shuffle(&mut thread_rng());
-        Exam {
-            name: name.to_string(),
+/// Shuffles `ex` with a generator derived from `master_seed` and the name of
+/// the produced version, so the same master exam, seed and name always give
+/// the same version.
+pub fn shuffle_exam_seeded(ex: &Exam, name: Option<&str>, master_seed: u64) -> Exam {
+    let version_name = if let Some(nm) = name { nm } else { &ex.name };
+    let mut rng = version_rng(master_seed, version_name);
+    shuffle_exam_with_rng(ex, name, &mut rng)
+}
+
+/// Shuffles `count` versions named after their [`version_code`], each with
+/// its own generator derived from `master_seed`.
+///
+/// With `options.pool` set, the questions of each version are drawn from the
+/// groups of `ex` first. With `options.dissimilarity` set, a version is drawn
+/// again until it is far enough from every version before it; it is an error
+/// when no candidate is found within the allowed attempts, as it is when
+/// `options.balance` cannot be met. Question templates
+/// get their own numbers in every version, see [`instantiate_with_rng`].
+pub fn shuffle_versions_seeded(
+    ex: &Exam,
+    count: usize,
+    master_seed: u64,
+    options: &ShuffleOptions,
+) -> Result<Vec<Exam>, ShuffleError> {
+    let shared_draw = match &options.pool {
+        Some(pool) if pool.same_for_all_versions => {
+            Some(pool.draw_with_rng(ex, &mut version_rng(master_seed, &ex.name))?)
+        }
+        _ => None,
+    };
+    let mut versions: Vec<Exam> = Vec::with_capacity(count);
+    for i in 0..count {
+        let code = version_code(i);
+        let mut rng = version_rng(master_seed, &code);
+        let own_draw = match (&options.pool, &shared_draw) {
+            (Some(pool), None) => Some(pool.draw_with_rng(ex, &mut rng)?),
+            _ => None,
+        };
+        let ex = shared_draw.as_ref().or(own_draw.as_ref()).unwrap_or(ex);
+        let ex = &instantiate_exam_with_rng(ex, &mut rng)?;
+        let mut version = shuffle_version_with_rng(ex, Some(&code), options, &mut rng)?;
+        if let Some(dissimilarity) = &options.dissimilarity {
+            let mut attempt = 1;
+            while !versions.iter().all(|v| dissimilarity.accepts(v, &version)) {
+                if attempt >= dissimilarity.attempts {
+                    return Err(ShuffleError::TooSimilar {
+                        version: code,
+                        attempts: dissimilarity.attempts,
+                    });
+                }
+                version = shuffle_version_with_rng(ex, Some(&code), options, &mut rng)?;
+                attempt += 1;
+            }
+        }
+        versions.push(version);
+    }
+    Ok(versions)
+}
+
+/// Shuffles one version of `ex` as described by `options`. It fails when
+/// `options.balance` cannot be met, see [`balanced_choices_with_rng`], or a
+/// question keeps more than `options.choices_per_question` choices, see
+/// [`shuffle_choices_sampled_with_rng`].
+pub fn shuffle_version_with_rng<R: Rng + ?Sized>(
+    ex: &Exam,
+    name: Option<&str>,
+    options: &ShuffleOptions,
+    rng: &mut R,
+) -> Result<Exam, ShuffleError> {
+    let shuffled = match &ex.questions {
+        Some(qs) => Some(match (&options.balance, options.choices_per_question) {
+            (Some(balance), _) => {
+                let ordering = group_ordering_with_rng(qs, options.groups, rng);
+                let qs_shuffled = balanced_choices_with_rng(
+                    qs,
+                    &ordering,
+                    balance,
+                    options.choices_per_question,
+                    rng,
+                )?;
+                (qs_shuffled, ordering)
+            }
+            (None, Some(count)) => {
+                let qs_shuffled: Vec<Question> = qs
+                    .iter()
+                    .map(|q| shuffle_choices_sampled_with_rng(q, count, rng))
+                    .collect::<Result<_, _>>()?;
+                let ordering = group_ordering_with_rng(qs, options.groups, rng);
+                (qs_shuffled, ordering)
+            }
+            (None, None) => shuffled_questions_with_rng(qs, options.groups, rng),
+        }),
+        None => None,
+    };
+    Ok(version_of(ex, name, shuffled))
+}
+
+pub fn shuffle_exam_with_rng<R: Rng + ?Sized>(ex: &Exam, name: Option<&str>, rng: &mut R) -> Exam {
+    shuffle_exam_grouped_with_rng(ex, name, GroupShuffle::Global, rng)
+}
+
+pub fn shuffle_exam_grouped(ex: &Exam, name: Option<&str>, mode: GroupShuffle) -> Exam {
+    shuffle_exam_grouped_with_rng(ex, name, mode, &mut thread_rng())
+}
+
+/// Like [`shuffle_exam_with_rng`], but the question ordering respects
+/// `Question::group` as described by `mode`.
+pub fn shuffle_exam_grouped_with_rng<R: Rng + ?Sized>(
+    ex: &Exam,
+    name: Option<&str>,
+    mode: GroupShuffle,
+    rng: &mut R,
+) -> Exam {
+    let shuffled = ex
+        .questions
+        .as_ref()
+        .map(|qs| shuffled_questions_with_rng(qs, mode, rng));
+    version_of(ex, name, shuffled)
+}
+
+/// Every question of `qs` with its choices shuffled, and the question
+/// ordering for `mode`.
+fn shuffled_questions_with_rng<R: Rng + ?Sized>(
+    qs: &[Question],
+    mode: GroupShuffle,
+    rng: &mut R,
+) -> (Vec<Question>, Vec<u32>) {
+    let qs_shuffled = qs
+        .iter()
+        .map(|q| shuffle_choices_with_rng(q, rng))
+        .collect();
+    (qs_shuffled, group_ordering_with_rng(qs, mode, rng))
+}
+
+/// The version of `ex` called `name` (or the name of `ex`) printing the
+/// `shuffled` questions in their ordering.
+fn version_of(ex: &Exam, name: Option<&str>, shuffled: Option<(Vec<Question>, Vec<u32>)>) -> Exam {
+    let name = name.unwrap_or(&ex.name).to_string();
+    match shuffled {
+        Some((questions, ordering)) => Exam {
+            name,
             preamble: ex.preamble.to_owned(),
-            questions: Some(qs_shuffled),
+            questions: Some(questions),
             ordering: Some(ordering),
-        }
-    } else {
-        Exam {
-            name: name.to_string(),
+        },
+        None => Exam {
+            name,
             preamble: None,
             questions: None,
             ordering: None,
-        }
+        },
     }
 }
 
 pub fn shuffle_choices(qs: &Question) -> Question {
+    shuffle_choices_with_rng(qs, &mut thread_rng())
+}
+
+pub fn shuffle_choices_with_rng<R: Rng + ?Sized>(qs: &Question, rng: &mut R) -> Question {
     if let Some(cs) = &qs.choices {
         let Choices(vcs, CorrectChoice(crrct), _) = cs;
-        let nocs = vcs.len() as u32;
-        let mut ordering: Vec<u32> = (0..nocs).collect();
-        ordering.shuffle(&mut thread_rng());
-        let new_order = (&ordering)
-            .iter()
-            .position(|o| o == crrct)
-            .unwrap_or(*crrct as usize);
-        let choice_ordering = ChoiceOrdering(ordering.to_vec());
-        let new_choices = Choices(
-            vcs.to_vec(),
-            CorrectChoice(new_order as u32),
-            Some(choice_ordering),
-        );
-        Question {
-            text: (qs.text).to_string(),
-            order: qs.order,
-            choices: Some(new_choices),
-            group: qs.group,
-        }
+        let ordering = choice_ordering_with_rng(vcs, rng);
+        with_choice_ordering(qs, vcs, *crrct, ordering)
     } else {
         qs.to_owned()
     }
 }
+
+pub fn shuffle_choices_sampled(qs: &Question, count: usize) -> Result<Question, ShuffleError> {
+    shuffle_choices_sampled_with_rng(qs, count, &mut thread_rng())
+}
+
+/// Like [`shuffle_choices_with_rng`], but prints at most `count` choices: the
+/// correct and pinned choices are always kept and the remaining places go to
+/// distractors sampled at random. The `ChoiceOrdering` lists the original
+/// index of every printed choice, so the dropped ones simply do not appear in
+/// it.
+///
+/// A pinned choice keeps its place among the kept choices, not its original
+/// index: it is printed after the kept choices written before it, e.g. a
+/// pinned last choice is still printed last. It is an error when the correct
+/// and pinned choices alone are more than `count`.
+pub fn shuffle_choices_sampled_with_rng<R: Rng + ?Sized>(
+    qs: &Question,
+    count: usize,
+    rng: &mut R,
+) -> Result<Question, ShuffleError> {
+    let (vcs, crrct) = match &qs.choices {
+        Some(Choices(vcs, CorrectChoice(crrct), _)) if vcs.len() > count => (vcs, *crrct),
+        _ => return Ok(shuffle_choices_with_rng(qs, rng)),
+    };
+    let required = |i: usize| vcs[i].pinned || vcs[i].correct || i == crrct as usize;
+    let distractors: Vec<usize> = (0..vcs.len()).filter(|i| !required(*i)).collect();
+    let mut kept: Vec<usize> = (0..vcs.len()).filter(|i| required(*i)).collect();
+    if kept.len() > count {
+        return Err(ShuffleError::TooManyKeptChoices {
+            question: qs.order,
+            kept: kept.len(),
+            count,
+        });
+    }
+    let nodistractors = count - kept.len();
+    kept.extend(
+        sample(rng, distractors.len(), nodistractors)
+            .into_iter()
+            .map(|i| distractors[i]),
+    );
+    kept.sort_unstable();
+    let kept_choices: Vec<Choice> = kept.iter().map(|i| vcs[*i].to_owned()).collect();
+    let ordering: Vec<u32> = choice_ordering_with_rng(&kept_choices, rng)
+        .into_iter()
+        .map(|k| kept[k as usize] as u32)
+        .collect();
+    Ok(with_choice_ordering(qs, vcs, crrct, ordering))
+}
+
+/// `qs` printing `vcs` in `ordering`, with the correct choice (originally at
+/// `crrct`) moved accordingly.
+fn with_choice_ordering(qs: &Question, vcs: &[Choice], crrct: u32, ordering: Vec<u32>) -> Question {
+    let new_order = ordering
+        .iter()
+        .position(|o| *o == crrct)
+        .unwrap_or(crrct as usize);
+    let new_choices = Choices(
+        vcs.to_vec(),
+        CorrectChoice(new_order as u32),
+        Some(ChoiceOrdering(ordering)),
+    );
+    Question {
+        text: (qs.text).to_string(),
+        order: qs.order,
+        choices: Some(new_choices),
+        group: qs.group,
+        metadata: qs.metadata.to_owned(),
+    }
+}
+
+/// Shuffled order of `vcs`, where pinned choices stay at their original
+/// index and the others are shuffled among the remaining positions.
+fn choice_ordering_with_rng<R: Rng + ?Sized>(vcs: &[Choice], rng: &mut R) -> Vec<u32> {
+    let free: Vec<u32> = (0..vcs.len() as u32)
+        .filter(|i| !vcs[*i as usize].pinned)
+        .collect();
+    let mut shuffled = free.clone();
+    shuffled.shuffle(rng);
+    let mut ordering: Vec<u32> = (0..vcs.len() as u32).collect();
+    free.iter()
+        .zip(shuffled)
+        .for_each(|(slot, o)| ordering[*slot as usize] = o);
+    ordering
+}
+
+/// Code printed on the cover of the version at `index` (0-based): `CODE01`,
+/// `CODE02`, ...
+pub fn version_code(index: usize) -> String {
+    format!("CODE{:02}", index + 1)
+}
+
+/// Derives the seed of a single version from the exam-level `master_seed` and
+/// the version name.
+///
+/// The hash (64-bit FNV-1a) is fixed so that seeds stay valid across
+/// compiler and crate releases.
+pub fn version_seed(master_seed: u64, version_name: &str) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+    master_seed
+        .to_le_bytes()
+        .iter()
+        .chain(version_name.as_bytes())
+        .fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// A portable generator for one version; see [`version_seed`].
+pub fn version_rng(master_seed: u64, version_name: &str) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(version_seed(master_seed, version_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exam() -> Exam {
+        let questions = (1..=10)
+            .map(|i| {
+                Question::new(
+                    &format!("question {}", i),
+                    i,
+                    Some(Choices(
+                        (1..=5)
+                            .map(|j| Choice::new(&format!("q{} option {}", i, j)))
+                            .collect(),
+                        CorrectChoice(0),
+                        None,
+                    )),
+                )
+            })
+            .collect();
+        Exam {
+            name: "master".to_string(),
+            preamble: None,
+            questions: Some(questions),
+            ordering: None,
+        }
+    }
+
+    #[test]
+    fn seeded_shuffle_is_reproducible() {
+        let ex = exam();
+        let v1 = shuffle_exam_seeded(&ex, Some("CODE01"), 2022);
+        let v1_again = shuffle_exam_seeded(&ex, Some("CODE01"), 2022);
+        assert_eq!(v1.ordering, v1_again.ordering);
+        assert_eq!(v1.questions, v1_again.questions);
+    }
+
+    #[test]
+    fn seeded_shuffle_differs_between_versions() {
+        let ex = exam();
+        let v1 = shuffle_exam_seeded(&ex, Some("CODE01"), 2022);
+        let v2 = shuffle_exam_seeded(&ex, Some("CODE02"), 2022);
+        assert_ne!(v1.questions, v2.questions);
+    }
+
+    #[test]
+    fn versions_keep_minimum_dissimilarity() {
+        let ex = exam();
+        let options = ShuffleOptions {
+            dissimilarity: Some(Dissimilarity {
+                min_position_change: 0.8,
+                min_answer_change: 0.6,
+                attempts: 1000,
+            }),
+            ..ShuffleOptions::default()
+        };
+        let versions = shuffle_versions_seeded(&ex, 4, 2022, &options).unwrap();
+        assert_eq!(versions.len(), 4);
+        for (i, a) in versions.iter().enumerate() {
+            for b in &versions[(i + 1)..] {
+                assert!(position_distance(a, b) >= 0.8);
+                assert!(answer_distance(a, b) >= 0.6);
+            }
+        }
+        let again = shuffle_versions_seeded(&ex, 4, 2022, &options).unwrap();
+        assert_eq!(
+            versions.iter().map(|v| &v.ordering).collect::<Vec<_>>(),
+            again.iter().map(|v| &v.ordering).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn versions_draw_from_pools() {
+        let mut ex = exam();
+        if let Some(qs) = ex.questions.as_mut() {
+            qs.iter_mut().for_each(|q| q.group = 1 + q.order % 2);
+        }
+        let pool = |same| QuestionPool::parse("1:2, 2:3", same).unwrap();
+        let masters = |v: &Exam| {
+            let mut orders: Vec<u32> = v.ordered_questions().iter().map(|q| q.order).collect();
+            orders.sort_unstable();
+            orders
+        };
+        let shared = ShuffleOptions {
+            pool: pool(true),
+            ..ShuffleOptions::default()
+        };
+        let versions = shuffle_versions_seeded(&ex, 3, 9, &shared).unwrap();
+        assert!(versions.iter().all(|v| v.ordered_questions().len() == 5));
+        assert!(versions.iter().all(|v| masters(v) == masters(&versions[0])));
+        let independent = ShuffleOptions {
+            pool: pool(false),
+            ..ShuffleOptions::default()
+        };
+        let versions = shuffle_versions_seeded(&ex, 6, 9, &independent).unwrap();
+        assert!(versions.iter().all(|v| v.ordered_questions().len() == 5));
+        assert!(versions.iter().any(|v| masters(v) != masters(&versions[0])));
+    }
+
+    #[test]
+    fn impossible_dissimilarity_is_an_error() {
+        let ex = exam();
+        let options = ShuffleOptions {
+            dissimilarity: Some(Dissimilarity {
+                min_position_change: 1.1,
+                min_answer_change: 0.0,
+                attempts: 10,
+            }),
+            ..ShuffleOptions::default()
+        };
+        assert!(shuffle_versions_seeded(&ex, 2, 1, &options).is_err());
+    }
+
+    #[test]
+    fn version_seed_depends_on_master_seed_and_name() {
+        assert_eq!(version_seed(1, "CODE01"), version_seed(1, "CODE01"));
+        assert_ne!(version_seed(1, "CODE01"), version_seed(2, "CODE01"));
+        assert_ne!(version_seed(1, "CODE01"), version_seed(1, "CODE02"));
+    }
+
+    fn grouped_exam() -> Exam {
+        let mut ex = exam();
+        if let Some(qs) = ex.questions.as_mut() {
+            qs.iter_mut().for_each(|q| q.group = 3 - (q.order - 1) % 3);
+        }
+        ex
+    }
+
+    fn groups_in_print_order(ex: &Exam) -> Vec<u32> {
+        ex.ordered_questions().iter().map(|q| q.group).collect()
+    }
+
+    #[test]
+    fn within_groups_keeps_groups_ascending() {
+        let ex = grouped_exam();
+        let mut rng = version_rng(3, "grouped");
+        let v = shuffle_exam_grouped_with_rng(&ex, None, GroupShuffle::WithinGroups, &mut rng);
+        assert_eq!(
+            groups_in_print_order(&v),
+            vec![1, 1, 1, 2, 2, 2, 3, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn shuffle_groups_keeps_groups_contiguous() {
+        let ex = grouped_exam();
+        let mut rng = version_rng(3, "grouped");
+        for _ in 0..10 {
+            let v = shuffle_exam_grouped_with_rng(&ex, None, GroupShuffle::ShuffleGroups, &mut rng);
+            let mut groups = groups_in_print_order(&v);
+            assert_eq!(groups.len(), 10);
+            groups.dedup();
+            assert_eq!(groups.len(), 3);
+        }
+    }
+
+    #[test]
+    fn shuffle_choices_tracks_correct_choice() {
+        let ex = exam();
+        let mut rng = version_rng(7, "test");
+        for q in ex.questions.unwrap() {
+            let shuffled = shuffle_choices_with_rng(&q, &mut rng);
+            let Choices(vcs, CorrectChoice(c), ordering) = shuffled.choices.unwrap();
+            let ChoiceOrdering(ordering) = ordering.unwrap();
+            let original = ordering[c as usize] as usize;
+            assert_eq!(vcs[original].text, format!("q{} option 1", q.order));
+        }
+    }
+
+    #[test]
+    fn shuffle_choices_tracks_all_correct_choices() {
+        let mut q = exam().questions.unwrap().remove(0);
+        if let Some(cs) = q.choices.as_mut() {
+            cs.0[0].correct = true;
+            cs.0[3].correct = true;
+        }
+        let mut rng = version_rng(5, "multiple");
+        for _ in 0..10 {
+            let cs = shuffle_choices_with_rng(&q, &mut rng).choices.unwrap();
+            let mut texts: Vec<String> = cs
+                .correct_positions()
+                .into_iter()
+                .map(|p| cs.ordered()[p as usize].text.to_owned())
+                .collect();
+            texts.sort();
+            assert_eq!(texts, vec!["q1 option 1", "q1 option 4"]);
+        }
+    }
+
+    #[test]
+    fn sampled_choices_keep_correct_and_pinned() {
+        let q = Question::new(
+            "pick one",
+            1,
+            Some(Choices(
+                vec![
+                    Choice::new("a"),
+                    Choice::new("b"),
+                    Choice::new("c"),
+                    Choice::new("d"),
+                    Choice::new("e"),
+                    Choice::new("f"),
+                    Choice::pinned("None of the above"),
+                ],
+                CorrectChoice(2),
+                None,
+            )),
+        );
+        let mut rng = version_rng(13, "sampled");
+        for _ in 0..20 {
+            let cs = shuffle_choices_sampled_with_rng(&q, 5, &mut rng)
+                .unwrap()
+                .choices
+                .unwrap();
+            let printed: Vec<&str> = cs.ordered().iter().map(|c| c.text.as_str()).collect();
+            assert_eq!(printed.len(), 5);
+            assert_eq!(printed[4], "None of the above");
+            assert_eq!(printed[cs.1 .0 as usize], "c");
+            let ChoiceOrdering(used) = cs.2.unwrap();
+            assert!(used.contains(&2) && used.contains(&6));
+        }
+    }
+
+    #[test]
+    fn sampled_pinned_choice_keeps_its_place_among_kept_choices() {
+        let q = Question::new(
+            "pick one",
+            1,
+            Some(Choices(
+                vec![
+                    Choice::new("a"),
+                    Choice::new("b"),
+                    Choice::pinned("c"),
+                    Choice::new("d"),
+                    Choice::new("e"),
+                    Choice::new("f"),
+                ],
+                CorrectChoice(0),
+                None,
+            )),
+        );
+        let mut rng = version_rng(5, "sampled");
+        for _ in 0..20 {
+            let cs = shuffle_choices_sampled_with_rng(&q, 3, &mut rng)
+                .unwrap()
+                .choices
+                .unwrap();
+            let ChoiceOrdering(used) = cs.2.as_ref().unwrap();
+            let before = used.iter().filter(|i| **i < 2).count();
+            assert_eq!(used[before], 2);
+        }
+    }
+
+    #[test]
+    fn sampling_fewer_choices_than_kept_is_an_error() {
+        let q = Question::new(
+            "pick two",
+            3,
+            Some(Choices(
+                vec![
+                    Choice::new("a"),
+                    Choice {
+                        correct: true,
+                        ..Choice::new("b")
+                    },
+                    Choice::pinned("c"),
+                    Choice::new("d"),
+                ],
+                CorrectChoice(0),
+                None,
+            )),
+        );
+        let err = shuffle_choices_sampled_with_rng(&q, 2, &mut version_rng(1, "sampled"));
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "question 3: 3 choices are correct or pinned, more than the 2 to print"
+        );
+    }
+
+    #[test]
+    fn pinned_choices_keep_their_position() {
+        let q = Question::new(
+            "pick one",
+            1,
+            Some(Choices(
+                vec![
+                    Choice::new("a"),
+                    Choice::new("b"),
+                    Choice::new("c"),
+                    Choice::new("d"),
+                    Choice::pinned("None of the above"),
+                ],
+                CorrectChoice(4),
+                None,
+            )),
+        );
+        let mut rng = version_rng(11, "pinned");
+        for _ in 0..20 {
+            let shuffled = shuffle_choices_with_rng(&q, &mut rng);
+            let cs = shuffled.choices.unwrap();
+            assert_eq!(cs.ordered()[4].text, "None of the above");
+            assert_eq!(cs.1, CorrectChoice(4));
+        }
+    }
+
+    #[test]
+    fn versions_instantiate_question_templates() {
+        let ex = Exam {
+            name: "master".to_string(),
+            preamble: None,
+            questions: Some(vec![Question::new(
+                "%{#vars} a = 1..1000 %{/vars}What is @{a} + 1?",
+                1,
+                Some(Choices(
+                    vec![Choice::new("@{a + 1}"), Choice::new("@{a + 2}")],
+                    CorrectChoice(0),
+                    None,
+                )),
+            )]),
+            ordering: None,
+        };
+        let options = ShuffleOptions::default();
+        let versions = shuffle_versions_seeded(&ex, 4, 5, &options).unwrap();
+        let texts: Vec<&str> = versions
+            .iter()
+            .map(|v| v.ordered_questions()[0].text.as_str())
+            .collect();
+        assert!(texts
+            .iter()
+            .all(|t| !t.contains('@') && !t.contains("%{#vars}")));
+        assert!(texts.windows(2).any(|w| w[0] != w[1]));
+        for v in &versions {
+            let q = v.ordered_questions()[0];
+            let a: u32 = q.text["What is ".len()..]
+                .split(' ')
+                .next()
+                .unwrap()
+                .parse()
+                .unwrap();
+            let cs = q.choices.as_ref().unwrap();
+            let correct = cs.ordered()[cs.correct_positions()[0] as usize];
+            assert_eq!(correct.text, (a + 1).to_string());
+        }
+        let again = shuffle_versions_seeded(&ex, 4, 5, &options).unwrap();
+        assert!(versions
+            .iter()
+            .zip(&again)
+            .all(|(v, w)| v.questions == w.questions));
+    }
+}
@@ -0,0 +1,263 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{shuffle_choices_sampled_with_rng, shuffle_choices_with_rng, Question};
+use crate::errors::ShuffleError;
+
+/// Constraints on where the correct answers of a version land.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnswerBalance {
+    /// The same letter is correct for at most `max_run` consecutive
+    /// questions.
+    pub max_run: usize,
+    /// How many times a letter may be correct beyond its fair share, i.e.
+    /// the number of questions it would be correct for on average.
+    pub tolerance: u32,
+    /// Shuffles tried per question before the version is given up.
+    pub attempts: u32,
+}
+
+impl Default for AnswerBalance {
+    fn default() -> Self {
+        AnswerBalance {
+            max_run: 2,
+            tolerance: 1,
+            attempts: 100,
+        }
+    }
+}
+
+impl AnswerBalance {
+    /// Most questions each letter may be correct for: its fair share rounded
+    /// up plus `tolerance`, counting every correct choice of a question. The
+    /// shares are sums of fractions, hence the small allowance before
+    /// rounding up.
+    fn letter_limits(&self, qs: &[Question], choices_per_question: Option<usize>) -> Vec<u32> {
+        let mut shares: Vec<f64> = vec![];
+        qs.iter().filter_map(|q| q.choices.as_ref()).for_each(|cs| {
+            let nocs = choices_per_question.map_or(cs.0.len(), |c| c.min(cs.0.len()));
+            let nocorrect = cs.correct_positions().len().min(nocs);
+            if shares.len() < nocs {
+                shares.resize(nocs, 0.0);
+            }
+            shares
+                .iter_mut()
+                .take(nocs)
+                .for_each(|s| *s += nocorrect as f64 / nocs as f64);
+        });
+        shares
+            .into_iter()
+            .map(|s| (s - 1e-9).ceil() as u32 + self.tolerance)
+            .collect()
+    }
+}
+
+/// Shuffles the choices of `qs` (returned in the same order as `qs`) visiting
+/// the questions in the printed `ordering`, so that the correct letters
+/// respect `balance`; every correct choice of a question counts towards its
+/// letter. With `choices_per_question` set, the choices are sampled as in
+/// [`shuffle_choices_sampled_with_rng`], which can fail.
+///
+/// Questions whose correct letters no shuffle can move, because the correct
+/// choices are pinned or at most one choice is free, are exempt from the
+/// constraints but still count towards the letters and runs of the others.
+/// It is an error when no shuffle of another question meets the constraints
+/// within `balance.attempts`.
+pub fn balanced_choices_with_rng<R: Rng + ?Sized>(
+    qs: &[Question],
+    ordering: &[u32],
+    balance: &AnswerBalance,
+    choices_per_question: Option<usize>,
+    rng: &mut R,
+) -> Result<Vec<Question>, ShuffleError> {
+    let shuffle = |q: &Question, rng: &mut R| match choices_per_question {
+        Some(count) => shuffle_choices_sampled_with_rng(q, count, rng),
+        None => Ok(shuffle_choices_with_rng(q, rng)),
+    };
+    let limits = balance.letter_limits(qs, choices_per_question);
+    let mut counts = vec![0u32; limits.len()];
+    let mut letters: Vec<Vec<u32>> = vec![];
+    let mut shuffled: Vec<Option<Question>> = vec![None; qs.len()];
+    for o in ordering {
+        let q = &qs[*o as usize];
+        let mut candidate = shuffle(q, rng)?;
+        let mut attempt = 1;
+        let exempt = fixed(q);
+        while !exempt && !fits(&candidate, &limits, &counts, &letters, balance.max_run) {
+            if attempt >= balance.attempts {
+                return Err(ShuffleError::Unbalanced {
+                    question: q.order,
+                    attempts: balance.attempts,
+                });
+            }
+            candidate = shuffle(q, rng)?;
+            attempt += 1;
+        }
+        if let Some(cs) = &candidate.choices {
+            let positions = cs.correct_positions();
+            positions.iter().for_each(|p| {
+                if let Some(count) = counts.get_mut(*p as usize) {
+                    *count += 1;
+                }
+            });
+            letters.push(positions);
+        }
+        shuffled[*o as usize] = Some(candidate);
+    }
+    Ok(shuffled
+        .into_iter()
+        .zip(qs)
+        .map(|(s, q)| s.unwrap_or_else(|| q.to_owned()))
+        .collect())
+}
+
+/// Whether no shuffle can move the correct choices of `q`.
+fn fixed(q: &Question) -> bool {
+    q.choices.as_ref().is_none_or(|cs| {
+        let free = cs.0.iter().filter(|c| !c.pinned).count();
+        let correct_pinned =
+            cs.0.iter()
+                .enumerate()
+                .filter(|(i, c)| c.correct || *i as u32 == cs.1 .0)
+                .all(|(_, c)| c.pinned);
+        free <= 1 || correct_pinned
+    })
+}
+
+fn fits(
+    q: &Question,
+    limits: &[u32],
+    counts: &[u32],
+    letters: &[Vec<u32>],
+    max_run: usize,
+) -> bool {
+    match &q.choices {
+        Some(cs) => cs.correct_positions().iter().all(|p| {
+            let i = *p as usize;
+            let under_limit = counts.get(i).zip(limits.get(i)).is_none_or(|(c, l)| c < l);
+            let run = letters.iter().rev().take_while(|l| l.contains(p)).count();
+            under_limit && run < max_run
+        }),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{version_rng, Choice, Choices, CorrectChoice};
+
+    fn questions(count: u32, nocs: u32) -> Vec<Question> {
+        (1..=count)
+            .map(|i| {
+                Question::new(
+                    &format!("question {}", i),
+                    i,
+                    Some(Choices(
+                        (0..nocs).map(|j| Choice::new(&j.to_string())).collect(),
+                        CorrectChoice(0),
+                        None,
+                    )),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn balanced_letters_respect_limits_and_runs() {
+        let qs = questions(20, 5);
+        let ordering: Vec<u32> = (0..20).collect();
+        let balance = AnswerBalance::default();
+        for seed in 0..20 {
+            let mut rng = version_rng(seed, "balanced");
+            let shuffled =
+                balanced_choices_with_rng(&qs, &ordering, &balance, None, &mut rng).unwrap();
+            let letters: Vec<u32> = shuffled
+                .iter()
+                .map(|q| q.choices.as_ref().unwrap().1 .0)
+                .collect();
+            for letter in 0..5 {
+                assert!(letters.iter().filter(|l| **l == letter).count() <= 5);
+            }
+            assert!(letters.windows(3).all(|w| !(w[0] == w[1] && w[1] == w[2])));
+        }
+    }
+
+    #[test]
+    fn letter_limits_follow_number_of_choices() {
+        let mut qs = questions(4, 4);
+        qs.extend(questions(2, 2));
+        let balance = AnswerBalance {
+            tolerance: 0,
+            ..AnswerBalance::default()
+        };
+        assert_eq!(balance.letter_limits(&qs, None), vec![2, 2, 1, 1]);
+        assert_eq!(balance.letter_limits(&qs, Some(2)), vec![3, 3]);
+    }
+
+    #[test]
+    fn balance_counts_every_correct_choice() {
+        let mut qs = questions(6, 3);
+        qs.iter_mut().for_each(|q| {
+            if let Some(cs) = q.choices.as_mut() {
+                cs.0[1].correct = true;
+            }
+        });
+        let ordering: Vec<u32> = (0..6).collect();
+        let balance = AnswerBalance {
+            max_run: 1,
+            tolerance: 0,
+            attempts: 1000,
+        };
+        assert_eq!(balance.letter_limits(&qs, None), vec![4, 4, 4]);
+        let mut rng = version_rng(7, "balanced");
+        let shuffled = balanced_choices_with_rng(&qs, &ordering, &balance, None, &mut rng);
+        assert!(matches!(
+            shuffled,
+            Err(ShuffleError::Unbalanced { question: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn balance_fails_when_attempts_run_out() {
+        let mut qs = questions(3, 2);
+        qs.iter_mut().for_each(|q| {
+            if let Some(cs) = q.choices.as_mut() {
+                cs.0[1].correct = true;
+            }
+        });
+        let ordering: Vec<u32> = (0..3).collect();
+        let mut rng = version_rng(1, "balanced");
+        let result =
+            balanced_choices_with_rng(&qs, &ordering, &AnswerBalance::default(), None, &mut rng);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "question 3: no shuffle of the choices met the answer balance in 100 attempts"
+        );
+    }
+
+    #[test]
+    fn balance_exempts_questions_whose_correct_letter_cannot_move() {
+        let mut qs = questions(3, 4);
+        qs.iter_mut().for_each(|q| {
+            if let Some(cs) = q.choices.as_mut() {
+                cs.0[0].pinned = true;
+            }
+        });
+        qs.extend(questions(2, 1));
+        qs.extend(questions(1, 4));
+        let ordering: Vec<u32> = (0..6).collect();
+        let balance = AnswerBalance {
+            attempts: 1000,
+            ..AnswerBalance::default()
+        };
+        let mut rng = version_rng(3, "balanced");
+        let shuffled = balanced_choices_with_rng(&qs, &ordering, &balance, None, &mut rng).unwrap();
+        let letters: Vec<Vec<u32>> = shuffled
+            .iter()
+            .map(|q| q.choices.as_ref().unwrap().correct_positions())
+            .collect();
+        assert_eq!(letters[..5], vec![vec![0]; 5]);
+        assert_ne!(letters[5], vec![0]);
+    }
+}
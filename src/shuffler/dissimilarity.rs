@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::Exam;
+
+/// Minimum distance required between every pair of generated versions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dissimilarity {
+    /// Fraction of questions that must be printed at different positions,
+    /// see [`position_distance`].
+    pub min_position_change: f64,
+    /// Fraction of printed positions whose correct answer must differ, see
+    /// [`answer_distance`].
+    pub min_answer_change: f64,
+    /// Candidates tried for each version before giving up.
+    pub attempts: u32,
+}
+
+impl Default for Dissimilarity {
+    fn default() -> Self {
+        Dissimilarity {
+            min_position_change: 0.5,
+            min_answer_change: 0.5,
+            attempts: 1000,
+        }
+    }
+}
+
+impl Dissimilarity {
+    pub fn accepts(&self, a: &Exam, b: &Exam) -> bool {
+        position_distance(a, b) >= self.min_position_change
+            && answer_distance(a, b) >= self.min_answer_change
+    }
+}
+
+/// Fraction of master questions printed at a different position in `a` and
+/// `b`; a question printed in only one of them counts as moved.
+pub fn position_distance(a: &Exam, b: &Exam) -> f64 {
+    let (pa, pb) = (printed_positions(a), printed_positions(b));
+    let mut masters: Vec<u32> = pa.keys().chain(pb.keys()).copied().collect();
+    masters.sort_unstable();
+    masters.dedup();
+    if masters.is_empty() {
+        return 0.0;
+    }
+    let moved = masters
+        .iter()
+        .filter(|m| !pa.contains_key(m) || pa.get(m) != pb.get(m))
+        .count();
+    moved as f64 / masters.len() as f64
+}
+
+/// Fraction of printed positions, among those holding a question with
+/// choices in both versions, whose correct answers differ.
+pub fn answer_distance(a: &Exam, b: &Exam) -> f64 {
+    let pairs: Vec<(Vec<u32>, Vec<u32>)> = a
+        .ordered_questions()
+        .into_iter()
+        .zip(b.ordered_questions())
+        .filter_map(|(qa, qb)| match (&qa.choices, &qb.choices) {
+            (Some(ca), Some(cb)) => Some((ca.correct_positions(), cb.correct_positions())),
+            _ => None,
+        })
+        .collect();
+    if pairs.is_empty() {
+        return 0.0;
+    }
+    let differing = pairs.iter().filter(|(x, y)| x != y).count();
+    differing as f64 / pairs.len() as f64
+}
+
+/// Printed position of every question, keyed by its master `order`.
+fn printed_positions(ex: &Exam) -> HashMap<u32, usize> {
+    ex.ordered_questions()
+        .into_iter()
+        .enumerate()
+        .map(|(p, q)| (q.order, p))
+        .collect()
+}
@@ -1,10 +1,18 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{errors::ExamReaderError, examreader, shuffler::Question};
+use std::fs;
+
+use crate::{
+    bank::Bank,
+    diagnostics::Diagnostic,
+    errors::{ExamReaderError, ExamWriterError},
+    examreader,
+    shuffler::Question,
+};
 
 use super::ExamSetting;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Exam {
     pub name: String,
     pub preamble: Option<String>,
@@ -21,6 +29,19 @@ impl Exam {
             ordering: None,
         }
     }
+    /// Questions in the order they are printed in this version.
+    pub fn ordered_questions(&self) -> Vec<&Question> {
+        match &self.questions {
+            Some(qs) => match &self.ordering {
+                Some(ordering) => ordering
+                    .iter()
+                    .filter_map(|o| qs.get(*o as usize))
+                    .collect(),
+                None => qs.iter().collect(),
+            },
+            None => vec![],
+        }
+    }
     pub fn from_tex(
         filename: &str,
         name: &str,
@@ -36,6 +57,62 @@ impl Exam {
             es,
         ))
     }
+    /// Reads a bank in the JSON format of [`Bank`].
+    pub fn from_json(filename: &str) -> Result<(Exam, Option<ExamSetting>), ExamReaderError> {
+        let content = fs::read_to_string(filename)?;
+        Bank::from_json_str(filename, &content)?.into_exam()
+    }
+
+    /// Reads a bank in the YAML format of [`Bank`].
+    pub fn from_yaml(filename: &str) -> Result<(Exam, Option<ExamSetting>), ExamReaderError> {
+        let content = fs::read_to_string(filename)?;
+        Bank::from_yaml_str(filename, &content)?.into_exam()
+    }
+
+    /// The exam as a JSON [`Bank`], questions and choices in printed order.
+    pub fn to_json(&self, setting: Option<&ExamSetting>) -> Result<String, ExamWriterError> {
+        Ok(serde_json::to_string_pretty(&Bank::from_exam(
+            self, setting,
+        ))?)
+    }
+
+    /// The exam as a YAML [`Bank`], questions and choices in printed order.
+    pub fn to_yaml(&self, setting: Option<&ExamSetting>) -> Result<String, ExamWriterError> {
+        Ok(serde_yaml_ng::to_string(&Bank::from_exam(self, setting))?)
+    }
+
+    pub fn write_json(
+        &self,
+        filename: &str,
+        setting: Option<&ExamSetting>,
+    ) -> Result<(), ExamWriterError> {
+        fs::write(filename, self.to_json(setting)?)?;
+        Ok(())
+    }
+
+    pub fn write_yaml(
+        &self,
+        filename: &str,
+        setting: Option<&ExamSetting>,
+    ) -> Result<(), ExamWriterError> {
+        fs::write(filename, self.to_yaml(setting)?)?;
+        Ok(())
+    }
+
+    /// Reads a QTI package, zipped or unpacked, with the warnings about the
+    /// items that were skipped.
+    pub fn from_qti(path: &str, name: &str) -> Result<(Exam, Vec<Diagnostic>), ExamReaderError> {
+        let (questions, warnings) = examreader::from_qti(path)?;
+        Ok((
+            Exam {
+                name: name.to_string(),
+                questions: Some(questions),
+                preamble: None,
+                ordering: None,
+            },
+            warnings,
+        ))
+    }
     pub fn from_csv(filename: &str, name: &str) -> Result<Exam, ExamReaderError> {
         let questions = examreader::from_csv(filename)?;
         Ok(Exam {
@@ -46,6 +123,20 @@ impl Exam {
         })
     }
 
+    pub fn from_xlsx(
+        filename: &str,
+        sheet: Option<&str>,
+        name: &str,
+    ) -> Result<Exam, ExamReaderError> {
+        let questions = examreader::from_xlsx(filename, sheet)?;
+        Ok(Exam {
+            name: name.to_string(),
+            questions: Some(questions),
+            preamble: None,
+            ordering: None,
+        })
+    }
+
     pub fn from_txt(filename: &str, name: &str) -> Result<Exam, ExamReaderError> {
         let questions = examreader::from_txt(filename)?;
         Ok(Exam {
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+/// Evaluates an arithmetic expression such as `2*a + sqrt(b)^2` with the
+/// values of `vars`.
+///
+/// Supported are numbers, variables, `+ - * / %`, `^` for powers,
+/// parentheses and the functions `abs`, `sqrt`, `exp`, `ln`, `log10`, `sin`,
+/// `cos`, `tan`, `floor`, `ceil`, `round`, `min`, `max` and `round(x, n)`.
+pub fn evaluate(expr: &str, vars: &HashMap<String, f64>) -> Result<f64, String> {
+    let tokens = tokenize(expr)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        vars,
+    };
+    let value = parser.expr()?;
+    if parser.pos < tokens.len() {
+        return Err(format!("unexpected `{}` in `{}`", tokens[parser.pos], expr));
+    }
+    if !value.is_finite() {
+        return Err(format!("`{}` is not a finite number", expr));
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(id) => write!(f, "{}", id),
+            Token::Op(op) => write!(f, "{}", op),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let value = number
+                .parse()
+                .map_err(|_| format!("invalid number `{}`", number))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*/%^(),".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return Err(format!("unexpected `{}` in `{}`", c, expr));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    vars: &'a HashMap<String, f64>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek_op(&self) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn expect_op(&mut self, op: char) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", op))
+        }
+    }
+
+    fn expr(&mut self) -> Result<f64, String> {
+        let mut value = self.term()?;
+        while let Some(op) = self.peek_op().filter(|op| *op == '+' || *op == '-') {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.peek_op().filter(|op| "*/%".contains(*op)) {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.peek_op() == Some('-') {
+            self.pos += 1;
+            return Ok(-self.unary()?);
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.primary()?;
+        if self.peek_op() == Some('^') {
+            self.pos += 1;
+            let exponent = self.unary()?;
+            return Ok(base.powf(exponent));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<f64, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                if self.peek_op() == Some('(') {
+                    self.pos += 1;
+                    let mut args = vec![self.expr()?];
+                    while self.peek_op() == Some(',') {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                    self.expect_op(')')?;
+                    call(&name, &args)
+                } else {
+                    self.vars
+                        .get(&name)
+                        .copied()
+                        .ok_or_else(|| format!("unknown variable `{}`", name))
+                }
+            }
+            Some(Token::Op('(')) => {
+                self.pos += 1;
+                let value = self.expr()?;
+                self.expect_op(')')?;
+                Ok(value)
+            }
+            Some(token) => Err(format!("unexpected `{}`", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn call(name: &str, args: &[f64]) -> Result<f64, String> {
+    let one = |f: fn(f64) -> f64| match args {
+        [x] => Ok(f(*x)),
+        _ => Err(format!("`{}` takes one argument", name)),
+    };
+    match name {
+        "abs" => one(f64::abs),
+        "sqrt" => one(f64::sqrt),
+        "exp" => one(f64::exp),
+        "ln" => one(f64::ln),
+        "log10" => one(f64::log10),
+        "sin" => one(f64::sin),
+        "cos" => one(f64::cos),
+        "tan" => one(f64::tan),
+        "floor" => one(f64::floor),
+        "ceil" => one(f64::ceil),
+        "round" => match args {
+            [x] => Ok(x.round()),
+            [x, n] => {
+                let factor = 10f64.powi(*n as i32);
+                Ok((x * factor).round() / factor)
+            }
+            _ => Err("`round` takes one or two arguments".to_string()),
+        },
+        "min" if !args.is_empty() => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        "max" if !args.is_empty() => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        _ => Err(format!("unknown function `{}`", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str) -> Result<f64, String> {
+        let vars: HashMap<String, f64> = [("a".to_string(), 3.0), ("b".to_string(), 4.0)]
+            .into_iter()
+            .collect();
+        evaluate(expr, &vars)
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(eval("-2^2"), Ok(-4.0));
+        assert_eq!(eval("2^3^2"), Ok(512.0));
+        assert_eq!(eval("7 % 4"), Ok(3.0));
+    }
+
+    #[test]
+    fn evaluates_variables_and_functions() {
+        assert_eq!(eval("sqrt(a^2 + b^2)"), Ok(5.0));
+        assert_eq!(eval("max(a, b, 1)"), Ok(4.0));
+        assert_eq!(eval("round(a / b, 1)"), Ok(0.8));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert!(eval("c + 1").is_err());
+        assert!(eval("1 +").is_err());
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("foo(1)").is_err());
+        assert!(eval("1 $ 2").is_err());
+    }
+}
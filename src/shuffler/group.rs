@@ -0,0 +1,51 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::Question;
+
+/// How the questions of an exam are reordered with respect to their
+/// `Question::group`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum GroupShuffle {
+    /// Questions are shuffled over the whole exam, groups are ignored.
+    #[default]
+    Global,
+    /// Questions are shuffled within their group and groups are kept in
+    /// ascending order.
+    WithinGroups,
+    /// Questions are shuffled within their group and the order of the groups
+    /// is shuffled too; each group stays contiguous.
+    ShuffleGroups,
+}
+
+/// Question ordering (indices into `qs`) for the given `mode`.
+pub fn group_ordering_with_rng<R: Rng + ?Sized>(
+    qs: &[Question],
+    mode: GroupShuffle,
+    rng: &mut R,
+) -> Vec<u32> {
+    if mode == GroupShuffle::Global {
+        let mut ordering: Vec<u32> = (0..qs.len() as u32).collect();
+        ordering.shuffle(rng);
+        return ordering;
+    }
+    let mut groups: Vec<u32> = qs.iter().map(|q| q.group).collect();
+    groups.sort_unstable();
+    groups.dedup();
+    if mode == GroupShuffle::ShuffleGroups {
+        groups.shuffle(rng);
+    }
+    let mut ordering = Vec::with_capacity(qs.len());
+    for group in groups {
+        let mut in_group: Vec<u32> = qs
+            .iter()
+            .enumerate()
+            .filter(|(_, q)| q.group == group)
+            .map(|(i, _)| i as u32)
+            .collect();
+        in_group.shuffle(rng);
+        ordering.extend(in_group);
+    }
+    ordering
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Exam, ExamSetting, QuestionPool};
+
+/// A problem in an exam that does not stop it from being read or shuffled
+/// but would show up on the printed versions. Questions are referred to by
+/// their master `order`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Lint {
+    NoChoices {
+        question: u32,
+    },
+    UnusualChoiceCount {
+        question: u32,
+        count: usize,
+        majority: usize,
+    },
+    DuplicateChoice {
+        question: u32,
+        text: String,
+    },
+    DuplicateQuestion {
+        question: u32,
+        first: u32,
+    },
+    EmptyPreamble,
+    NoVersions,
+    InvalidGroups(String),
+    UnusedGroup(u32),
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::NoChoices { question } => write!(f, "question {} has no choices", question),
+            Lint::UnusualChoiceCount {
+                question,
+                count,
+                majority,
+            } => write!(
+                f,
+                "question {} has {} choices while most questions have {}",
+                question, count, majority
+            ),
+            Lint::DuplicateChoice { question, text } => {
+                write!(f, "question {} has the choice `{}` twice", question, text)
+            }
+            Lint::DuplicateQuestion { question, first } => {
+                write!(f, "question {} repeats question {}", question, first)
+            }
+            Lint::EmptyPreamble => write!(f, "the preamble is empty"),
+            Lint::NoVersions => write!(f, "the number of versions is not set"),
+            Lint::InvalidGroups(err) => write!(f, "the groups setting is invalid: {}", err),
+            Lint::UnusedGroup(group) => {
+                write!(f, "group {} of the groups setting has no questions", group)
+            }
+        }
+    }
+}
+
+/// Texts compared with their surrounding and repeated whitespace ignored.
+fn normalized(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+impl Exam {
+    /// Checks the exam for problems that would only show up when
+    /// proofreading the printed versions.
+    pub fn validate(&self) -> Vec<Lint> {
+        let mut lints = vec![];
+        if self.preamble.as_ref().is_some_and(|p| p.trim().is_empty()) {
+            lints.push(Lint::EmptyPreamble);
+        }
+        let qs = self.questions.as_deref().unwrap_or_default();
+
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        qs.iter()
+            .filter_map(|q| q.choices.as_ref())
+            .filter(|cs| !cs.0.is_empty())
+            .for_each(|cs| *counts.entry(cs.0.len()).or_default() += 1);
+        let majority = counts
+            .iter()
+            .max_by_key(|(len, n)| (**n, **len))
+            .map(|(len, _)| *len);
+
+        let mut seen_questions: HashMap<String, u32> = HashMap::new();
+        for q in qs {
+            match q.choices.as_ref().filter(|cs| !cs.0.is_empty()) {
+                None => lints.push(Lint::NoChoices { question: q.order }),
+                Some(cs) => {
+                    if let Some(majority) = majority.filter(|m| *m != cs.0.len()) {
+                        lints.push(Lint::UnusualChoiceCount {
+                            question: q.order,
+                            count: cs.0.len(),
+                            majority,
+                        });
+                    }
+                    let mut seen_choices: Vec<String> = vec![];
+                    for c in &cs.0 {
+                        let text = normalized(&c.text);
+                        if seen_choices.contains(&text) {
+                            lints.push(Lint::DuplicateChoice {
+                                question: q.order,
+                                text,
+                            });
+                        } else {
+                            seen_choices.push(text);
+                        }
+                    }
+                }
+            }
+            match seen_questions.get(&normalized(&q.text)) {
+                Some(first) => lints.push(Lint::DuplicateQuestion {
+                    question: q.order,
+                    first: *first,
+                }),
+                None => {
+                    seen_questions.insert(normalized(&q.text), q.order);
+                }
+            }
+        }
+        lints
+    }
+
+    /// Checks the exam `setting` of a TeX template or bank against the exam.
+    pub fn validate_setting(&self, setting: &ExamSetting) -> Vec<Lint> {
+        let mut lints = vec![];
+        let qs = self.questions.as_deref().unwrap_or_default();
+        if setting.numberofvestions == 0 {
+            lints.push(Lint::NoVersions);
+        }
+        match QuestionPool::parse(&setting.groups, true) {
+            Ok(Some(pool)) => pool
+                .draws
+                .iter()
+                .filter(|(group, _)| qs.iter().all(|q| q.group != *group))
+                .for_each(|(group, _)| lints.push(Lint::UnusedGroup(*group))),
+            Ok(None) => (),
+            Err(err) => lints.push(Lint::InvalidGroups(err.to_string())),
+        }
+        lints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{Choice, Choices, CorrectChoice, Question};
+
+    fn choices(texts: &[&str]) -> Option<Choices> {
+        Some(Choices(
+            texts.iter().map(|t| Choice::new(t)).collect(),
+            CorrectChoice(0),
+            None,
+        ))
+    }
+
+    #[test]
+    fn clean_exam_has_no_lints() {
+        let ex = Exam {
+            name: "master".to_string(),
+            preamble: Some("\\usepackage{amsfonts}".to_string()),
+            questions: Some(vec![
+                Question::new("one", 1, choices(&["a", "b"])),
+                Question::new("two", 2, choices(&["a", "b"])),
+            ]),
+            ordering: None,
+        };
+        let setting = ExamSetting {
+            numberofvestions: 2,
+            groups: "1:2".to_string(),
+            ..ExamSetting::new()
+        };
+        assert_eq!(ex.validate(), vec![]);
+        assert_eq!(ex.validate_setting(&setting), vec![]);
+    }
+
+    #[test]
+    fn reports_question_problems() {
+        let ex = Exam {
+            name: "master".to_string(),
+            preamble: Some(" ".to_string()),
+            questions: Some(vec![
+                Question::new("one", 1, choices(&["a", "b", "c"])),
+                Question::new("two", 2, choices(&["a", "b", "c"])),
+                Question::new("three", 3, choices(&["a", " b", "b "])),
+                Question::new("four", 4, choices(&["a", "b"])),
+                Question::new("five", 5, None),
+                Question::new("  one", 6, choices(&["x", "y", "z"])),
+                Question::new("seven", 7, choices(&[])),
+                Question::new("eight", 8, choices(&[])),
+                Question::new("nine", 9, choices(&[])),
+                Question::new("ten", 10, choices(&[])),
+            ]),
+            ordering: None,
+        };
+        assert_eq!(
+            ex.validate(),
+            vec![
+                Lint::EmptyPreamble,
+                Lint::DuplicateChoice {
+                    question: 3,
+                    text: "b".to_string()
+                },
+                Lint::UnusualChoiceCount {
+                    question: 4,
+                    count: 2,
+                    majority: 3
+                },
+                Lint::NoChoices { question: 5 },
+                Lint::DuplicateQuestion {
+                    question: 6,
+                    first: 1
+                },
+                Lint::NoChoices { question: 7 },
+                Lint::NoChoices { question: 8 },
+                Lint::NoChoices { question: 9 },
+                Lint::NoChoices { question: 10 },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_setting_problems() {
+        let ex = Exam {
+            name: "master".to_string(),
+            preamble: None,
+            questions: Some(vec![Question::new("one", 1, choices(&["a", "b"]))]),
+            ordering: None,
+        };
+        let setting = ExamSetting {
+            groups: "1:1, 3:2".to_string(),
+            ..ExamSetting::new()
+        };
+        assert_eq!(
+            ex.validate_setting(&setting),
+            vec![Lint::NoVersions, Lint::UnusedGroup(3)]
+        );
+        let setting = ExamSetting {
+            numberofvestions: 1,
+            groups: "1-1".to_string(),
+            ..ExamSetting::new()
+        };
+        assert!(matches!(
+            ex.validate_setting(&setting)[..],
+            [Lint::InvalidGroups(_)]
+        ));
+    }
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+use super::{AnswerBalance, Dissimilarity, GroupShuffle, QuestionPool};
+
+/// Everything that shapes how a version is shuffled beyond the generator.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ShuffleOptions {
+    pub groups: GroupShuffle,
+    /// When set, choices are shuffled so that the correct letters are spread
+    /// evenly over the version.
+    pub balance: Option<AnswerBalance>,
+    /// When set, generated versions keep a minimum distance from each other.
+    pub dissimilarity: Option<Dissimilarity>,
+    /// When set, each version holds only the questions drawn from the pools.
+    pub pool: Option<QuestionPool>,
+    /// When set, each question prints at most this many choices, see
+    /// [`super::shuffle_choices_sampled_with_rng`].
+    pub choices_per_question: Option<usize>,
+}
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::{evaluate, Choices, Exam, Question};
+use crate::constants::{PARAM_CLOSE, PARAM_OPEN, TEX_VARS_END, TEX_VARS_START};
+use crate::errors::ShuffleError;
+
+/// Attempts at drawing the variables of a question before giving up on
+/// choices that keep evaluating to the same text.
+const DRAW_ATTEMPTS: u32 = 100;
+
+/// How the value of a template variable is obtained.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableKind {
+    /// `lo..hi` or `lo..hi step s`: one of `lo, lo + s, ...` up to `hi`.
+    Range {
+        lo: String,
+        hi: String,
+        step: Option<String>,
+    },
+    /// `[e1, e2, ...]`: one of the listed expressions.
+    List(Vec<String>),
+    /// Any other expression, computed from the variables before it.
+    Computed(String),
+}
+
+/// A variable declared as `name = ...` in the `%{#vars}` block of a question.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub kind: VariableKind,
+}
+
+impl Variable {
+    fn parse(declaration: &str) -> Result<Self, String> {
+        let (name, value) = declaration
+            .split_once('=')
+            .ok_or_else(|| format!("`{}` is not a `name = value` declaration", declaration))?;
+        let name = name.trim();
+        let valid_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(format!("invalid variable name `{}`", name));
+        }
+        let value = value.trim();
+        let kind = if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let items: Vec<String> = split_top_level(items)
+                .into_iter()
+                .map(|i| i.trim().to_string())
+                .filter(|i| !i.is_empty())
+                .collect();
+            if items.is_empty() {
+                return Err(format!("the list of `{}` is empty", name));
+            }
+            VariableKind::List(items)
+        } else if let Some((lo, rest)) = value.split_once("..") {
+            let (hi, step) = match rest.split_once("step") {
+                Some((hi, step)) => (hi, Some(step.trim().to_string())),
+                None => (rest, None),
+            };
+            VariableKind::Range {
+                lo: lo.trim().to_string(),
+                hi: hi.trim().to_string(),
+                step,
+            }
+        } else {
+            VariableKind::Computed(value.to_string())
+        };
+        Ok(Variable {
+            name: name.to_string(),
+            kind,
+        })
+    }
+
+    fn draw_with_rng<R: Rng + ?Sized>(
+        &self,
+        vars: &HashMap<String, f64>,
+        rng: &mut R,
+    ) -> Result<f64, String> {
+        match &self.kind {
+            VariableKind::Range { lo, hi, step } => {
+                let lo = evaluate(lo, vars)?;
+                let hi = evaluate(hi, vars)?;
+                let step = match step {
+                    Some(step) => evaluate(step, vars)?,
+                    None => 1.0,
+                };
+                if step <= 0.0 || hi < lo {
+                    return Err(format!("the range of `{}` is empty", self.name));
+                }
+                let count = ((hi - lo) / step + 1e-9).floor() as u64;
+                Ok(lo + step * rng.gen_range(0..=count) as f64)
+            }
+            VariableKind::List(items) => {
+                let item = items.choose(rng).expect("lists are never empty");
+                evaluate(item, vars)
+            }
+            VariableKind::Computed(expr) => evaluate(expr, vars),
+        }
+    }
+}
+
+/// Splits `text` at commas outside of parentheses.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Splits a question text into its variable declarations and the text
+/// without the `%{#vars}` block.
+///
+/// Declarations are separated by new lines or `;`; the `%` starting a line
+/// of a TeX template is ignored.
+pub fn split_variables(text: &str) -> Result<(Vec<Variable>, String), String> {
+    let Some(start) = text.find(TEX_VARS_START) else {
+        return Ok((vec![], text.to_string()));
+    };
+    let end = text[start..]
+        .find(TEX_VARS_END)
+        .map(|e| start + e)
+        .ok_or_else(|| format!("`{}` is never closed", TEX_VARS_START))?;
+    let variables = text[start + TEX_VARS_START.len()..end]
+        .split(['\n', ';'])
+        .map(|d| d.trim().trim_start_matches('%').trim())
+        .filter(|d| !d.is_empty())
+        .map(Variable::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let rest = format!("{}{}", &text[..start], &text[end + TEX_VARS_END.len()..]);
+    Ok((variables, rest.trim().to_string()))
+}
+
+/// The question text without its `%{#vars}` block, as printed in the master.
+pub fn strip_variables(text: &str) -> String {
+    match split_variables(text) {
+        Ok((_, rest)) => rest,
+        Err(_) => text.to_string(),
+    }
+}
+
+/// Whether `q` is a question template, i.e. declares its variables in a
+/// `%{#vars}` block. The `@{...}` of other questions, such as the column
+/// specifiers of a LaTeX `array`, are plain text.
+pub fn is_parametric(q: &Question) -> bool {
+    q.text.contains(TEX_VARS_START)
+}
+
+/// Replaces every `@{expr}` in `text` by the value of `expr`, the
+/// placeholder ending at the brace matching its opening one. A placeholder
+/// written `@{expr:N}` is printed with `N` decimals; an empty `@{}` is left
+/// as it is.
+pub fn substitute(text: &str, vars: &HashMap<String, f64>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(PARAM_OPEN) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + PARAM_OPEN.len()..];
+        let end =
+            matching_close(after).ok_or_else(|| format!("`{}` is never closed", PARAM_OPEN))?;
+        let placeholder = &after[..end];
+        if placeholder.trim().is_empty() {
+            out.push_str(&rest[start..start + PARAM_OPEN.len() + end + PARAM_CLOSE.len()]);
+            rest = &after[end + PARAM_CLOSE.len()..];
+            continue;
+        }
+        let (expr, decimals) = match placeholder.rsplit_once(':') {
+            Some((expr, decimals)) => {
+                let decimals = decimals
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid number of decimals in `{}`", placeholder))?;
+                (expr, Some(decimals))
+            }
+            None => (placeholder, None),
+        };
+        out.push_str(&format_value(evaluate(expr, vars)?, decimals));
+        rest = &after[end + PARAM_CLOSE.len()..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Index in `text` of the `}` closing a brace opened just before it.
+fn matching_close(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Whole numbers are printed without decimals and other numbers with at
+/// most six, unless `decimals` is given.
+fn format_value(value: f64, decimals: Option<usize>) -> String {
+    match decimals {
+        Some(decimals) => format!("{:.*}", decimals, value),
+        None if (value - value.round()).abs() < 1e-9 => format!("{}", value.round() as i64),
+        None => {
+            let text = format!("{:.6}", value);
+            text.trim_end_matches('0').trim_end_matches('.').to_string()
+        }
+    }
+}
+
+/// Draws the variables of a question template and evaluates its text and
+/// choices with them. Questions that are not templates are returned as they
+/// are, without using `rng`.
+///
+/// The variables are drawn again when two choices would print the same.
+pub fn instantiate_with_rng<R: Rng + ?Sized>(
+    q: &Question,
+    rng: &mut R,
+) -> Result<Question, ShuffleError> {
+    if !is_parametric(q) {
+        return Ok(q.clone());
+    }
+    let error = |message: String| ShuffleError::Parameter {
+        question: q.order,
+        message,
+    };
+    let (variables, text) = split_variables(&q.text).map_err(error)?;
+    for _ in 0..DRAW_ATTEMPTS {
+        let mut vars = HashMap::new();
+        for variable in &variables {
+            let value = variable.draw_with_rng(&vars, rng).map_err(error)?;
+            vars.insert(variable.name.to_owned(), value);
+        }
+        let choices = match &q.choices {
+            Some(Choices(vcs, crrct, ordering)) => {
+                let mut instantiated = vcs.to_owned();
+                for c in instantiated.iter_mut() {
+                    c.text = substitute(&c.text, &vars).map_err(error)?;
+                }
+                let distinct = instantiated
+                    .iter()
+                    .enumerate()
+                    .all(|(i, c)| instantiated[..i].iter().all(|d| d.text != c.text));
+                if !distinct {
+                    continue;
+                }
+                Some(Choices(instantiated, crrct.to_owned(), ordering.to_owned()))
+            }
+            None => None,
+        };
+        return Ok(Question {
+            text: substitute(&text, &vars).map_err(error)?,
+            choices,
+            ..q.to_owned()
+        });
+    }
+    Err(error(format!(
+        "two choices print the same in {} draws",
+        DRAW_ATTEMPTS
+    )))
+}
+
+/// Instantiates every question template of `ex`, see [`instantiate_with_rng`].
+pub fn instantiate_exam_with_rng<R: Rng + ?Sized>(
+    ex: &Exam,
+    rng: &mut R,
+) -> Result<Exam, ShuffleError> {
+    let questions = match &ex.questions {
+        Some(qs) => Some(
+            qs.iter()
+                .map(|q| instantiate_with_rng(q, rng))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => None,
+    };
+    Ok(Exam {
+        questions,
+        ..ex.to_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{version_rng, Choice, CorrectChoice};
+
+    fn template() -> Question {
+        Question::new(
+            "%{#vars}\n% a = 2..9\n% b = [10, 20, 30]; c = a * b\n%{/vars}\nCompute $@{a} \\times @{b}$.",
+            1,
+            Some(Choices(
+                vec![
+                    Choice::new("$@{c}$"),
+                    Choice::new("$@{c + a}$"),
+                    Choice::new("$@{c / 4:2}$"),
+                ],
+                CorrectChoice(0),
+                None,
+            )),
+        )
+    }
+
+    #[test]
+    fn parses_declarations() {
+        let (variables, text) = split_variables(&template().text).unwrap();
+        assert_eq!(text, "Compute $@{a} \\times @{b}$.");
+        let kinds: Vec<VariableKind> = variables.into_iter().map(|v| v.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                VariableKind::Range {
+                    lo: "2".to_string(),
+                    hi: "9".to_string(),
+                    step: None
+                },
+                VariableKind::List(vec!["10".to_string(), "20".to_string(), "30".to_string()]),
+                VariableKind::Computed("a * b".to_string()),
+            ]
+        );
+        assert!(split_variables("%{#vars} 1a = 2 %{/vars}").is_err());
+        assert!(split_variables("%{#vars} a = 1").is_err());
+    }
+
+    #[test]
+    fn instantiates_text_and_choices() {
+        let mut rng = version_rng(3, "CODE01");
+        let q = instantiate_with_rng(&template(), &mut rng).unwrap();
+        let a: f64 = q.text["Compute $".len()..]
+            .split(' ')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let b: f64 = q
+            .text
+            .split("\\times ")
+            .nth(1)
+            .unwrap()
+            .trim_end_matches("$.")
+            .parse()
+            .unwrap();
+        assert!((2.0..=9.0).contains(&a) && [10.0, 20.0, 30.0].contains(&b));
+        let cs = q.choices.unwrap();
+        assert_eq!(cs.0[0].text, format!("${}$", a * b));
+        assert_eq!(cs.0[1].text, format!("${}$", a * b + a));
+        assert_eq!(cs.0[2].text, format!("${:.2}$", a * b / 4.0));
+        assert_eq!(cs.1, CorrectChoice(0));
+    }
+
+    #[test]
+    fn ranges_with_steps_and_formatting() {
+        let q = Question::new(
+            "%{#vars} x = 0.5..1.5 step 0.5 %{/vars}@{x}, @{1/3}",
+            1,
+            None,
+        );
+        let mut rng = version_rng(1, "CODE01");
+        for _ in 0..20 {
+            let text = instantiate_with_rng(&q, &mut rng).unwrap().text;
+            let (x, third) = text.split_once(", ").unwrap();
+            assert!(["0.5", "1", "1.5"].contains(&x));
+            assert_eq!(third, "0.333333");
+        }
+    }
+
+    #[test]
+    fn plain_questions_are_untouched() {
+        let q = Question::new("What is $1+1$?", 1, None);
+        assert!(!is_parametric(&q));
+        assert_eq!(
+            instantiate_with_rng(&q, &mut version_rng(1, "CODE01")).unwrap(),
+            q
+        );
+    }
+
+    #[test]
+    fn latex_at_braces_are_not_placeholders() {
+        let array = "$\\begin{array}{l@{}r} 1 & 2 \\end{array}$";
+        let q = Question::new(
+            array,
+            1,
+            Some(Choices(vec![Choice::new("@{x}")], CorrectChoice(0), None)),
+        );
+        assert!(!is_parametric(&q));
+        assert_eq!(
+            instantiate_with_rng(&q, &mut version_rng(1, "CODE01")).unwrap(),
+            q
+        );
+
+        let q = Question::new(
+            &format!(
+                "%{{#vars}} a = 2 %{{/vars}}{} $\\frac{{@{{a}}}}{{@{{a + 1}}}}$",
+                array
+            ),
+            1,
+            None,
+        );
+        let text = instantiate_with_rng(&q, &mut version_rng(1, "CODE01"))
+            .unwrap()
+            .text;
+        assert_eq!(text, format!("{} $\\frac{{2}}{{3}}$", array));
+    }
+
+    #[test]
+    fn reports_coinciding_choices() {
+        let q = Question::new(
+            "%{#vars} a = 1..3 %{/vars}Pick",
+            4,
+            Some(Choices(
+                vec![Choice::new("@{a}"), Choice::new("@{a * 1}")],
+                CorrectChoice(0),
+                None,
+            )),
+        );
+        let err = instantiate_with_rng(&q, &mut version_rng(1, "CODE01")).unwrap_err();
+        assert!(matches!(err, ShuffleError::Parameter { question: 4, .. }));
+    }
+}
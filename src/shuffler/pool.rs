@@ -0,0 +1,156 @@
+use rand::seq::index::sample;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{Exam, Question};
+use crate::errors::ShuffleError;
+
+/// Draws a number of questions from each group, treating every
+/// `Question::group` as a pool. Groups without a draw keep all their
+/// questions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuestionPool {
+    /// `(group, number of questions drawn from it)`
+    pub draws: Vec<(u32, usize)>,
+    /// All versions share one drawn subset when `true`; otherwise each
+    /// version draws its own.
+    pub same_for_all_versions: bool,
+}
+
+impl QuestionPool {
+    /// Parses draws written as in the `groups` key of the exam setting,
+    /// e.g. `1:3, 2:5, 3:2`. An empty text means no pool.
+    pub fn parse(groups: &str, same_for_all_versions: bool) -> Result<Option<Self>, ShuffleError> {
+        let draws = groups
+            .split(',')
+            .map(|d| d.trim())
+            .filter(|d| !d.is_empty())
+            .map(|d| {
+                let parsed = d
+                    .split_once(':')
+                    .and_then(|(g, n)| Some((g.trim().parse().ok()?, n.trim().parse().ok()?)));
+                parsed.ok_or_else(|| ShuffleError::InvalidPool(d.to_string()))
+            })
+            .collect::<Result<Vec<(u32, usize)>, ShuffleError>>()?;
+        if draws.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(QuestionPool {
+            draws,
+            same_for_all_versions,
+        }))
+    }
+
+    /// A copy of `ex` holding only the drawn questions, kept in their master
+    /// order and with their master `order`.
+    pub fn draw_with_rng<R: Rng + ?Sized>(
+        &self,
+        ex: &Exam,
+        rng: &mut R,
+    ) -> Result<Exam, ShuffleError> {
+        let qs = match &ex.questions {
+            Some(qs) => qs,
+            None => return Ok(ex.clone()),
+        };
+        let mut keep: Vec<bool> = vec![true; qs.len()];
+        for (group, count) in &self.draws {
+            let in_group: Vec<usize> = qs
+                .iter()
+                .enumerate()
+                .filter(|(_, q)| q.group == *group)
+                .map(|(i, _)| i)
+                .collect();
+            if in_group.len() < *count {
+                return Err(ShuffleError::PoolTooSmall {
+                    group: *group,
+                    requested: *count,
+                    available: in_group.len(),
+                });
+            }
+            in_group.iter().for_each(|i| keep[*i] = false);
+            sample(rng, in_group.len(), *count)
+                .into_iter()
+                .for_each(|i| keep[in_group[i]] = true);
+        }
+        let drawn: Vec<Question> = qs
+            .iter()
+            .zip(keep)
+            .filter(|(_, k)| *k)
+            .map(|(q, _)| q.to_owned())
+            .collect();
+        Ok(Exam {
+            name: ex.name.to_owned(),
+            preamble: ex.preamble.to_owned(),
+            questions: Some(drawn),
+            ordering: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffler::{shuffle_versions_seeded, version_rng, ShuffleOptions};
+
+    fn bank() -> Exam {
+        let questions = (1..=12)
+            .map(|i| {
+                let mut q = Question::from(&format!("question {}", i), i);
+                q.group = (i - 1) % 3 + 1;
+                q
+            })
+            .collect();
+        Exam {
+            name: "bank".to_string(),
+            preamble: None,
+            questions: Some(questions),
+            ordering: None,
+        }
+    }
+
+    #[test]
+    fn parse_groups_setting() {
+        let pool = QuestionPool::parse("1:3, 2:5,3:2", true).unwrap().unwrap();
+        assert_eq!(pool.draws, vec![(1, 3), (2, 5), (3, 2)]);
+        assert_eq!(QuestionPool::parse("  ", true).unwrap(), None);
+        assert!(QuestionPool::parse("1:3, two:5", true).is_err());
+    }
+
+    #[test]
+    fn draw_keeps_requested_counts() {
+        let pool = QuestionPool::parse("1:2, 2:1", true).unwrap().unwrap();
+        let drawn = pool
+            .draw_with_rng(&bank(), &mut version_rng(1, "pool"))
+            .unwrap();
+        let qs = drawn.questions.unwrap();
+        let count = |g: u32| qs.iter().filter(|q| q.group == g).count();
+        assert_eq!((count(1), count(2), count(3)), (2, 1, 4));
+        assert!(qs.windows(2).all(|w| w[0].order < w[1].order));
+    }
+
+    #[test]
+    fn groups_setting_draws_from_tex_groups() {
+        let (master, setting) =
+            Exam::from_tex("files/testing/template-groups.tex", "master").unwrap();
+        let groups: Vec<u32> = master.ordered_questions().iter().map(|q| q.group).collect();
+        assert_eq!(groups, vec![1, 1, 2, 2, 2]);
+        let options = ShuffleOptions {
+            pool: QuestionPool::parse(&setting.unwrap().groups, false).unwrap(),
+            ..ShuffleOptions::default()
+        };
+        let versions = shuffle_versions_seeded(&master, 3, 7, &options).unwrap();
+        for version in versions {
+            let qs = version.ordered_questions();
+            let count = |g: u32| qs.iter().filter(|q| q.group == g).count();
+            assert_eq!((count(1), count(2)), (1, 2));
+        }
+    }
+
+    #[test]
+    fn draw_more_than_available_is_an_error() {
+        let pool = QuestionPool::parse("1:5", true).unwrap().unwrap();
+        assert!(pool
+            .draw_with_rng(&bank(), &mut version_rng(1, "pool"))
+            .is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Question {
@@ -6,6 +7,10 @@ pub struct Question {
     pub order: u32,
     pub choices: Option<Choices>,
     pub group: u32,
+    /// Extra fields carried along from the input, e.g. `Points` or `Topic`
+    /// columns of a CSV file.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl Question {
@@ -15,6 +20,7 @@ impl Question {
             order,
             choices,
             group: 1,
+            metadata: BTreeMap::new(),
         }
     }
     pub fn from(text: &str, order: u32) -> Self {
@@ -23,6 +29,7 @@ impl Question {
             order,
             choices: None,
             group: 1,
+            metadata: BTreeMap::new(),
         }
     }
 }
@@ -34,14 +41,105 @@ pub struct Choices(
     pub Option<ChoiceOrdering>,
 );
 
+impl Choices {
+    /// Choices in the order they are printed, following the `ChoiceOrdering`
+    /// when the question has been shuffled.
+    pub fn ordered(&self) -> Vec<&Choice> {
+        match &self.2 {
+            Some(ChoiceOrdering(ordering)) => ordering
+                .iter()
+                .filter_map(|o| self.0.get(*o as usize))
+                .collect(),
+            None => self.0.iter().collect(),
+        }
+    }
+
+    /// Printed positions of all correct choices: the `CorrectChoice` together
+    /// with every choice flagged as `Choice::correct`, in ascending order.
+    /// Empty when there are no choices.
+    pub fn correct_positions(&self) -> Vec<u32> {
+        let CorrectChoice(crrct) = self.1;
+        self.ordered()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, c)| c.correct || *i as u32 == crrct)
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
+    /// Score of a response selecting the printed positions `selected`.
+    pub fn score(&self, selected: &[u32], scoring: Scoring) -> f64 {
+        scoring.score(&self.correct_positions(), selected)
+    }
+}
+
+/// How a question with several correct choices is graded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Scoring {
+    /// 1 when exactly the correct choices are selected, 0 otherwise.
+    #[default]
+    AllOrNothing,
+    /// Every selected correct choice adds and every selected wrong choice
+    /// removes `1 / (number of correct choices)`; the score never drops
+    /// below 0.
+    Partial,
+}
+
+impl Scoring {
+    /// Score of selecting `selected` when `correct` are the correct
+    /// positions; nothing is correct about a question without any.
+    pub fn score(&self, correct: &[u32], selected: &[u32]) -> f64 {
+        if correct.is_empty() {
+            return 0.0;
+        }
+        let mut selected = selected.to_vec();
+        selected.sort_unstable();
+        selected.dedup();
+        match self {
+            Scoring::AllOrNothing => {
+                let mut correct = correct.to_vec();
+                correct.sort_unstable();
+                correct.dedup();
+                if correct == selected {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Scoring::Partial => {
+                let right = selected.iter().filter(|s| correct.contains(s)).count() as f64;
+                let wrong = selected.len() as f64 - right;
+                ((right - wrong) / correct.len() as f64).max(0.0)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Choice {
     pub text: String,
+    /// A pinned choice (e.g. "None of the above") keeps its position when
+    /// the choices are shuffled.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Marks an additional correct choice of a question that accepts more
+    /// than one answer; see [`Choices::correct_positions`].
+    #[serde(default)]
+    pub correct: bool,
 }
 impl Choice {
     pub fn new(text: &str) -> Choice {
         Choice {
             text: String::from(text),
+            pinned: false,
+            correct: false,
+        }
+    }
+    pub fn pinned(text: &str) -> Choice {
+        Choice {
+            text: String::from(text),
+            pinned: true,
+            correct: false,
         }
     }
 }
@@ -100,6 +198,24 @@ mod tests {
         assert_eq!(q.choices, opts);
     }
 
+    #[test]
+    fn question_correct_positions() {
+        let mut q = question();
+        if let Some(cs) = q.choices.as_mut() {
+            cs.0[3].correct = true;
+            cs.2 = Some(ChoiceOrdering(vec![3, 4, 2, 1, 0]));
+            cs.1 = CorrectChoice(3);
+        }
+        let cs = q.choices.unwrap();
+        assert_eq!(cs.correct_positions(), vec![0, 3]);
+        assert_eq!(cs.score(&[3, 0], Scoring::AllOrNothing), 1.0);
+        assert_eq!(cs.score(&[0], Scoring::AllOrNothing), 0.0);
+        assert_eq!(cs.score(&[0], Scoring::Partial), 0.5);
+        assert_eq!(cs.score(&[0, 1], Scoring::Partial), 0.0);
+        let none = Choices(vec![], CorrectChoice(0), None);
+        assert!(none.correct_positions().is_empty());
+    }
+
     #[test]
     fn question_from() {
         let q = Question::from("question from", 2);
@@ -108,6 +224,7 @@ mod tests {
             choices: None,
             order: 2,
             group: 1,
+            metadata: BTreeMap::new(),
         };
         assert_eq!(q, q2);
     }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct ExamSetting {
     pub university: String,
     pub department: String,
@@ -13,6 +14,12 @@ pub struct ExamSetting {
     pub groups: String,
 }
 
+impl Default for ExamSetting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ExamSetting {
     pub fn new() -> Self {
         ExamSetting {